@@ -0,0 +1,43 @@
+use crate::cpu_render;
+use crate::palette::Palette;
+use crate::view::View;
+use crate::vk_render::device::DeviceChoice;
+use crate::vk_render::Renderer;
+
+/// What draws the images of the offline renders
+pub enum Backend {
+    Gpu(Box<Renderer>),
+    Cpu,
+}
+
+impl Backend {
+    /// The GPU chosen by `device`, unless `cpu` is set or there is none
+    pub fn new(cpu: bool, device: &DeviceChoice, palette: &Palette) -> Backend {
+        if cpu {
+            return Backend::Cpu;
+        }
+        match Renderer::headless(device, palette) {
+            Ok(renderer) => Backend::Gpu(Box::new(renderer)),
+            Err(e) => {
+                eprintln!("{}\nRendering on the CPU", e);
+                Backend::Cpu
+            }
+        }
+    }
+
+    /// RGBA8 rows, top row first
+    pub fn render(
+        &mut self,
+        view: &View,
+        palette: &Palette,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, String> {
+        match self {
+            Backend::Gpu(renderer) => renderer
+                .render_offscreen(view, width, height)
+                .map_err(|e| e.to_string()),
+            Backend::Cpu => Ok(cpu_render::render(view, palette, width, height)),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::path::Path;
+use std::process;
+
+use crate::cli;
+use crate::cpu_render;
+use crate::png_io::write_png;
+
+/// Renders a single image on the CPU and writes it to disk, no window nor GPU involved
+pub fn render(args: &clap::ArgMatches) {
+    let mut location = cli::location(args).unwrap_or_else(|e| {
+        eprintln!("Could not read the location: {}", e);
+        process::exit(1);
+    });
+    let palette = cli::load_palette(args, &mut location.palette).unwrap_or_else(|e| {
+        eprintln!("Could not load the palette {}", e);
+        process::exit(1);
+    });
+    let [width, height] = cli::parse_pair(args.value_of("size").unwrap(), 'x');
+    let output = Path::new(args.value_of("output").unwrap());
+
+    let pixels = cpu_render::render(&location.view, &palette, width, height);
+    if let Err(e) = write_png(output, width, height, &pixels, &location) {
+        eprintln!("Could not write {}: {}", output.display(), e);
+        process::exit(1);
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("render")
+        .about("Renders an image on the CPU and writes it as a PNG file")
+        .args(&cli::view_args())
+        .arg(
+            clap::Arg::with_name("size")
+                .long("size")
+                .value_name("WIDTHxHEIGHT")
+                .help("Size of the image, in pixels")
+                .default_value("800x800")
+                .validator(|v| cli::validate_size(&v)),
+        )
+        .arg(
+            clap::Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .help("Path of the PNG file to write")
+                .required(true),
+        )
+}
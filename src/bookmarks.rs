@@ -0,0 +1,157 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::location::Location;
+
+/// Named locations, saved in the config directory as a list of sections:
+/// ```text
+/// [seahorse valley]
+/// center = -0.75, 0.1
+/// zoom = 0.05
+///
+/// [elephants]
+/// ...
+/// ```
+/// See `Location` for the content of the sections.
+#[derive(Clone, Default)]
+pub struct Bookmarks {
+    pub entries: Vec<(String, Location)>,
+}
+
+/// `fractol/bookmarks.txt` in the config directory of the user
+pub fn path() -> Result<PathBuf, String> {
+    match dirs::config_dir() {
+        Some(dir) => Ok(dir.join("fractol").join("bookmarks.txt")),
+        None => Err("no config directory for this user".to_owned()),
+    }
+}
+
+impl Bookmarks {
+    /// No bookmarks if the file does not exist yet
+    pub fn load() -> Result<Bookmarks, String> {
+        let path = path()?;
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Bookmarks::default()),
+            Err(e) => return Err(format!("{}: {}", path.display(), e)),
+        };
+        Bookmarks::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn parse(text: &str) -> Result<Bookmarks, String> {
+        let mut entries = Vec::new();
+        let mut lines = text.lines().enumerate().peekable();
+        while let Some((n, line)) = lines.next() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let name = match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                Some(name) => name.trim().to_owned(),
+                None => return Err(format!("line {}: expected '[name]', got '{}'", n + 1, line)),
+            };
+            let mut section = Vec::new();
+            while let Some(&(n, line)) = lines.peek() {
+                if line.trim_start().starts_with('[') {
+                    break;
+                }
+                section.push((n, line));
+                lines.next();
+            }
+            entries.push((name, Location::parse(section.into_iter())?));
+        }
+        Ok(Bookmarks { entries })
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = path()?;
+        let error = |e: io::Error| format!("{}: {}", path.display(), e);
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(error)?;
+        }
+        // written aside then renamed, so that quitting while saving can't leave half a file
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, self.text()).map_err(|e| format!("{}: {}", tmp.display(), e))?;
+        fs::rename(&tmp, &path).map_err(error)
+    }
+
+    /// The content of the file, that `parse` reads back
+    fn text(&self) -> String {
+        let sections: Vec<String> = self
+            .entries
+            .iter()
+            .map(|(name, location)| format!("[{}]\n{}", name, location))
+            .collect();
+        sections.join("\n")
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Location> {
+        let name = name.trim();
+        self.entries.iter().find(|(n, _)| n == name).map(|(_, l)| l)
+    }
+
+    /// Replaces the bookmark with the same name, if any. Names are trimmed, as `load` does,
+    /// and must fit in a `[name]` line
+    pub fn insert(&mut self, name: &str, location: Location) -> Result<(), String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err("a bookmark needs a name".to_owned());
+        }
+        if name.contains([']', '\n', '\r']) {
+            return Err(format!(
+                "'{}': a bookmark name can't contain ']' nor line breaks",
+                name
+            ));
+        }
+        match self.entries.iter_mut().find(|(n, _)| n == name) {
+            Some(entry) => entry.1 = location,
+            None => self.entries.push((name.to_owned(), location)),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::view::View;
+
+    fn location(zoom: f64) -> Location {
+        let mut view = View::default();
+        view.camera.zoom = zoom;
+        Location {
+            view,
+            palette: None,
+        }
+    }
+
+    #[test]
+    fn insert_trims_and_replaces() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.insert("seahorses", location(0.5)).unwrap();
+        bookmarks.insert("  seahorses ", location(0.25)).unwrap();
+        assert_eq!(bookmarks.entries.len(), 1);
+        assert_eq!(bookmarks.get("seahorses").unwrap().view.camera.zoom, 0.25);
+    }
+
+    #[test]
+    fn insert_rejects_names_that_do_not_read_back() {
+        let mut bookmarks = Bookmarks::default();
+        for name in ["", "   ", "a]b", "two\nlines"] {
+            assert!(bookmarks.insert(name, location(0.5)).is_err(), "{:?}", name);
+        }
+        assert!(bookmarks.entries.is_empty());
+    }
+
+    #[test]
+    fn parses_what_it_writes() {
+        let mut bookmarks = Bookmarks::default();
+        bookmarks.insert("[first", location(0.5)).unwrap();
+        bookmarks.insert("second one", location(1e-3)).unwrap();
+        let parsed = Bookmarks::parse(&bookmarks.text()).unwrap();
+        let names: Vec<&str> = parsed.entries.iter().map(|(n, _)| n.as_str()).collect();
+        assert_eq!(names, ["[first", "second one"]);
+        assert_eq!(parsed.get("second one").unwrap().view.camera.zoom, 1e-3);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::double_double::DoubleDouble;
+use std::f32::consts::TAU;
+
+pub const MIN_ZOOM: f64 = 1e-30; // past this, even the double-double center is too coarse
+pub const MAX_ZOOM: f64 = 2.;
+
+/// Top speeds, reached after holding a key for a few 1 / DAMPING seconds
+const PAN_SPEED: f64 = 2.; // screen units per second
+const ZOOM_SPEED: f64 = 3.; // zoom e-folds per second
+const ROTATION_SPEED: f64 = 1.; // radians per second
+/// How fast speeds follow the input, and glide to a stop once it is released (1 / second)
+const DAMPING: f64 = 8.;
+
+/// Movements asked by the user, each between -1 and 1
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CameraInput {
+    /// screen direction to move toward
+    pub pan: [f64; 2],
+    /// 1 zooms in, -1 zooms out
+    pub zoom: f64,
+    /// 1 turns clockwise
+    pub rotate: f64,
+}
+
+/// `angle` plus or minus whole turns, to be at most half a turn from `reference`, so that
+/// going from one to the other turns the short way. Unchanged if it already is.
+pub fn nearest_turn(angle: f64, reference: f64) -> f64 {
+    let tau = f64::from(TAU);
+    angle - ((angle - reference) / tau).round() * tau
+}
+
+/// Follows `target` for `dt` seconds, starting at `velocity`: returns the distance
+/// traveled and the new velocity. Integrated exactly, so a step of 2 * dt
+/// ends up at the same place as 2 steps of dt.
+fn glide(velocity: f64, target: f64, dt: f64) -> (f64, f64) {
+    let decay = (-DAMPING * dt).exp();
+    let distance = target * dt + (velocity - target) * (1. - decay) / DAMPING;
+    (distance, target + (velocity - target) * decay)
+}
+
+/// Which part of the complex plane is on screen.
+/// Screen coordinates go from -1 (top) to 1 (bottom) vertically, and as much
+/// as the aspect ratio allows horizontally, like `shaders/fractal.vert`.
+#[derive(Clone, Copy, Debug)]
+pub struct Camera {
+    /// point of the complex plane at the center of the screen
+    pub center: [DoubleDouble; 2],
+    /// half of the height of the complex plane shown
+    pub zoom: f64,
+    /// angle in radians, turning the view clockwise on screen
+    pub rotation: f32,
+    /// per second, in screen units, zoom e-folds (positive zooms in) and radians
+    pub pan_velocity: [f64; 2],
+    pub zoom_velocity: f64,
+    pub rotation_velocity: f64,
+}
+
+impl Camera {
+    pub fn new(center: [DoubleDouble; 2], zoom: f64) -> Camera {
+        Camera {
+            center,
+            zoom,
+            rotation: 0.,
+            pan_velocity: [0., 0.],
+            zoom_velocity: 0.,
+            rotation_velocity: 0.,
+        }
+    }
+
+    /// Advances the camera by `dt` seconds: velocities accelerate toward what
+    /// `input` asks for and decay back to 0 once it is released
+    pub fn step(&mut self, dt: f64, input: &CameraInput) {
+        let (pan_x, vx) = glide(self.pan_velocity[0], input.pan[0] * PAN_SPEED, dt);
+        let (pan_y, vy) = glide(self.pan_velocity[1], input.pan[1] * PAN_SPEED, dt);
+        let (zoom, vz) = glide(self.zoom_velocity, input.zoom * ZOOM_SPEED, dt);
+        let (rotation, vr) = glide(self.rotation_velocity, input.rotate * ROTATION_SPEED, dt);
+        self.pan_velocity = [vx, vy];
+        self.zoom_velocity = vz;
+        self.rotation_velocity = vr;
+
+        self.pan([pan_x, pan_y]);
+        self.zoom *= (-zoom).exp();
+        if !(MIN_ZOOM..=MAX_ZOOM).contains(&self.zoom) {
+            self.zoom = self.zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+            self.zoom_velocity = 0.;
+        }
+        self.rotation = (self.rotation + rotation as f32) % TAU;
+    }
+
+    /// Complex plane vector corresponding to the screen vector `v`
+    pub fn screen_to_plane(&self, v: [f64; 2]) -> [f64; 2] {
+        let (sin, cos) = f64::from(self.rotation).sin_cos();
+        [
+            (v[0] * cos - v[1] * sin) * self.zoom,
+            (v[0] * sin + v[1] * cos) * self.zoom,
+        ]
+    }
+
+    /// Moves the center by the screen vector `v`
+    pub fn pan(&mut self, v: [f64; 2]) {
+        let d = self.screen_to_plane(v);
+        self.center = [self.center[0] + d[0], self.center[1] + d[1]];
+    }
+
+    /// Multiplies the zoom by `factor`, keeping the point at `screen` in place
+    pub fn zoom_at(&mut self, screen: [f64; 2], factor: f64) {
+        let factor = factor.clamp(MIN_ZOOM / self.zoom, MAX_ZOOM / self.zoom);
+        self.pan([screen[0] * (1. - factor), screen[1] * (1. - factor)]);
+        self.zoom *= factor;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> Camera {
+        Camera::new([DoubleDouble::from(-1.), DoubleDouble::from(0.)], 0.5)
+    }
+
+    #[test]
+    fn step_does_not_depend_on_the_frame_rate() {
+        let input = CameraInput {
+            pan: [1., -0.5],
+            ..CameraInput::default()
+        };
+        let mut slow = camera();
+        let mut fast = camera();
+        slow.step(0.1, &input);
+        for _ in 0..20 {
+            fast.step(0.005, &input);
+        }
+        assert!((slow.center[0] - fast.center[0]).abs().hi < 1e-12);
+        assert!((slow.center[1] - fast.center[1]).abs().hi < 1e-12);
+        assert!((slow.pan_velocity[0] - fast.pan_velocity[0]).abs() < 1e-12);
+
+        let input = CameraInput {
+            zoom: 1.,
+            ..CameraInput::default()
+        };
+        slow.step(0.1, &input);
+        for _ in 0..20 {
+            fast.step(0.005, &input);
+        }
+        assert!((slow.zoom / fast.zoom - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn glides_to_a_stop() {
+        let mut camera = camera();
+        let input = CameraInput {
+            pan: [1., 0.],
+            ..CameraInput::default()
+        };
+        camera.step(1., &input);
+        assert!((camera.pan_velocity[0] - PAN_SPEED).abs() < 1e-3);
+        let released = camera.center[0];
+        camera.step(1., &CameraInput::default());
+        assert!(camera.center[0].hi > released.hi);
+        assert!(camera.pan_velocity[0].abs() < 1e-3);
+        let stopped = camera.center[0];
+        camera.step(1., &CameraInput::default());
+        assert!((camera.center[0] - stopped).abs().hi < 1e-4);
+    }
+
+    #[test]
+    fn zoom_stays_in_bounds() {
+        let mut camera = camera();
+        let input = CameraInput {
+            zoom: -1.,
+            ..CameraInput::default()
+        };
+        camera.step(10., &input);
+        assert_eq!(camera.zoom, MAX_ZOOM);
+        assert_eq!(camera.zoom_velocity, 0.);
+    }
+}
@@ -0,0 +1,299 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use clap::Arg;
+
+use crate::bookmarks::Bookmarks;
+use crate::camera;
+use crate::coloring::Coloring;
+use crate::double_double::DoubleDouble;
+use crate::fractal::{self, Formula};
+use crate::location::{self, Location};
+use crate::palette::{self, Palette};
+use crate::view;
+use crate::vk_render::device::DeviceChoice;
+
+/// Arguments choosing what to draw, shared by the viewer and the `render` subcommand.
+/// They apply on top of `--location` or `--bookmark`, if any.
+pub fn view_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("location")
+            .long("location")
+            .value_name("FILE")
+            .help("Starts from a location file, in the format of the bookmarks, or from a PNG saved by fractol")
+            .conflicts_with("bookmark"),
+        Arg::with_name("bookmark")
+            .long("bookmark")
+            .value_name("NAME")
+            .help("Starts from a saved bookmark"),
+        Arg::with_name("center")
+            .long("center")
+            .value_name("X,Y")
+            .help("Point of the complex plane at the center of the image [default: -1,0]")
+            .allow_hyphen_values(true)
+            .validator(|v| validate_pair::<DoubleDouble>(&v, ',')),
+        Arg::with_name("zoom")
+            .long("zoom")
+            .value_name("ZOOM")
+            .help("Half of the height of the complex plane shown [default: 0.5]")
+            .validator(|v| validate_zoom(&v)),
+        Arg::with_name("rotation")
+            .long("rotation")
+            .value_name("DEGREES")
+            .help("Turns the view clockwise [default: 0]")
+            .allow_hyphen_values(true)
+            .validator(|v| validate_finite::<f64>(&v)),
+        Arg::with_name("julia")
+            .long("julia")
+            .value_name("X,Y")
+            .help("Draws the Julia set of this parameter instead of the Mandelbrot set")
+            .allow_hyphen_values(true)
+            .validator(|v| validate_finite_pair::<f32>(&v)),
+        Arg::with_name("formula")
+            .long("formula")
+            .value_name("NAME")
+            .help("mandelbrot, burning-ship, tricorn or multibrot [default: mandelbrot]")
+            .validator(|v| v.parse::<Formula>().map(|_| ())),
+        Arg::with_name("power")
+            .long("power")
+            .value_name("N")
+            .help("Exponent of the multibrot formula [default: 3]")
+            .validator(|v| validate_power(&v)),
+        Arg::with_name("iterations")
+            .long("iterations")
+            .value_name("N")
+            .help("Iterations before a point is considered inside the set [default: 100]")
+            .validator(|v| validate_iterations(&v)),
+        Arg::with_name("auto-iterations")
+            .long("auto-iterations")
+            .help("Scales the iterations with the zoom depth, --iterations being the count at zoom 1/e"),
+        Arg::with_name("coloring")
+            .long("coloring")
+            .value_name("NAME")
+            .help("lighting or smooth [default: lighting]")
+            .validator(|v| v.parse::<Coloring>().map(|_| ())),
+        Arg::with_name("palette")
+            .long("palette")
+            .value_name("FILE")
+            .help("Gradient file of the smooth coloring: .map, .ugr, or one 'POSITION RED GREEN BLUE' stop per line"),
+        Arg::with_name("palette-offset")
+            .long("palette-offset")
+            .value_name("T")
+            .help("Shifts the palette, 1 being a whole turn [default: 0]")
+            .allow_hyphen_values(true)
+            .validator(|v| validate_finite::<f32>(&v)),
+        Arg::with_name("palette-period")
+            .long("palette-period")
+            .value_name("N")
+            .help("Iterations over which the palette spreads before repeating [default: 50]")
+            .validator(|v| validate_period(&v)),
+    ]
+}
+
+/// Which GPU to use, for the viewer and the subcommands drawing on the GPU
+pub fn device_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("device")
+        .long("device")
+        .value_name("DEVICE")
+        .help("GPU to draw with: a number from --list-devices, part of its name, or discrete, integrated, virtual or cpu [default: the first GPU that works, discrete ones first]")
+        .env("FRACTOL_DEVICE")
+        .validator(|v| v.parse::<DeviceChoice>().map(|_| ()))
+}
+
+/// The device chosen by the `device_arg`
+pub fn device(args: &clap::ArgMatches) -> DeviceChoice {
+    args.value_of("device").map_or(DeviceChoice::Auto, parse)
+}
+
+/// The location described by the `view_args`
+pub fn location(args: &clap::ArgMatches) -> Result<Location, String> {
+    let mut location = if let Some(path) = args.value_of("location") {
+        Location::load(Path::new(path))?
+    } else if let Some(name) = args.value_of("bookmark") {
+        let bookmarks = Bookmarks::load()?;
+        match bookmarks.get(name) {
+            Some(location) => location.clone(),
+            None => return Err(format!("no bookmark named '{}'", name)),
+        }
+    } else {
+        Location {
+            view: Default::default(),
+            palette: None,
+        }
+    };
+
+    let view = &mut location.view;
+    let value = |name| args.value_of(name);
+    if let Some(v) = value("center") {
+        view.camera.center = parse_pair(v, ',');
+    }
+    if let Some(v) = value("zoom") {
+        view.camera.zoom = parse(v);
+    }
+    if let Some(v) = value("rotation") {
+        view.camera.rotation = location::radians(parse(v));
+    }
+    if let Some(v) = value("julia") {
+        view.julia = true;
+        view.julia_c = parse_pair(v, ',');
+    }
+    if let Some(v) = value("formula") {
+        view.formula = parse(v);
+    }
+    if let Some(v) = value("power") {
+        view.power = parse(v);
+    }
+    if let Some(v) = value("iterations") {
+        view.max_iter = parse(v);
+    }
+    if args.is_present("auto-iterations") {
+        view.auto_iter = true;
+    }
+    if let Some(v) = value("coloring") {
+        view.coloring = parse(v);
+    }
+    if let Some(v) = value("palette-offset") {
+        view.palette_offset = parse(v);
+    }
+    if let Some(v) = value("palette-period") {
+        view.palette_period = parse(v);
+    }
+    if let Some(v) = value("palette") {
+        location.palette = Some(PathBuf::from(v));
+    }
+    Ok(location)
+}
+
+/// The palette of a `Location`. If its file can't be loaded it is an error when given by
+/// `--palette`, but when it comes from a location file or a PNG (shared by someone else, it
+/// names a file of their computer) the default palette is used, forgetting `path` then so that
+/// it says what is drawn
+pub fn load_palette(
+    args: &clap::ArgMatches,
+    path: &mut Option<PathBuf>,
+) -> Result<Palette, String> {
+    let explicit = args
+        .value_of("palette")
+        .is_some_and(|p| path.as_deref() == Some(Path::new(p)));
+    match path.as_deref().map(Palette::load) {
+        Some(Ok(palette)) => Ok(palette),
+        Some(Err(e)) if explicit => Err(e),
+        Some(Err(e)) => {
+            eprintln!("Could not load the palette {}, using the default one", e);
+            *path = None;
+            Ok(Palette::default())
+        }
+        None => Ok(Palette::default()),
+    }
+}
+
+/// For values already checked by a validator
+pub fn parse<T: FromStr>(s: &str) -> T {
+    match s.trim().parse() {
+        Ok(v) => v,
+        Err(_) => unreachable!("validated by clap"),
+    }
+}
+
+pub fn parse_pair<T: FromStr>(s: &str, sep: char) -> [T; 2] {
+    match split_pair(s, sep) {
+        Some(pair) => pair,
+        None => unreachable!("validated by clap"),
+    }
+}
+
+fn split_pair<T: FromStr>(s: &str, sep: char) -> Option<[T; 2]> {
+    let mut it = s.splitn(2, sep);
+    let a = it.next()?.trim().parse().ok()?;
+    let b = it.next()?.trim().parse().ok()?;
+    Some([a, b])
+}
+
+/// Rejects the infinities and NaN, as well as the numbers too big for `T`
+pub fn validate_finite<T: FromStr + Into<f64> + Copy>(s: &str) -> Result<(), String> {
+    match s.trim().parse::<T>() {
+        Ok(v) if v.into().is_finite() => Ok(()),
+        _ => Err(format!("expected a finite number, got '{}'", s)),
+    }
+}
+
+/// Two finite numbers separated by a ','
+pub fn validate_finite_pair<T: FromStr + Into<f64> + Copy>(s: &str) -> Result<(), String> {
+    match split_pair::<T>(s, ',') {
+        Some(pair) if pair.iter().all(|&v| v.into().is_finite()) => Ok(()),
+        _ => Err(format!(
+            "expected two finite numbers separated by ',', got '{}'",
+            s
+        )),
+    }
+}
+
+fn validate_pair<T: FromStr>(s: &str, sep: char) -> Result<(), String> {
+    match split_pair::<T>(s, sep) {
+        Some(_) => Ok(()),
+        None => Err(format!(
+            "expected two values separated by '{}', got '{}'",
+            sep, s
+        )),
+    }
+}
+
+pub fn validate_size(s: &str) -> Result<(), String> {
+    match split_pair::<u32>(s, 'x') {
+        Some([w, h]) if w > 0 && h > 0 => Ok(()),
+        _ => Err(format!("expected a size like 1920x1080, got '{}'", s)),
+    }
+}
+
+fn validate_range<T: FromStr + PartialOrd + std::fmt::Display>(
+    s: &str,
+    min: T,
+    max: T,
+) -> Result<(), String> {
+    match s.parse::<T>() {
+        Ok(v) if min <= v && v <= max => Ok(()),
+        _ => Err(format!(
+            "expected a number between {} and {}, got '{}'",
+            min, max, s
+        )),
+    }
+}
+
+fn validate_power(s: &str) -> Result<(), String> {
+    validate_range(s, fractal::MIN_POWER, fractal::MAX_POWER)
+}
+
+fn validate_iterations(s: &str) -> Result<(), String> {
+    validate_range(s, view::MIN_ITERATIONS, view::MAX_ITERATIONS)
+}
+
+fn validate_period(s: &str) -> Result<(), String> {
+    validate_range(s, palette::MIN_PERIOD, palette::MAX_PERIOD)
+}
+
+pub fn validate_count(s: &str) -> Result<(), String> {
+    match s.parse::<u32>() {
+        Ok(n) if n > 0 => Ok(()),
+        _ => Err(format!("expected a positive integer, got '{}'", s)),
+    }
+}
+
+/// Largest `--screenshot-scale`: 16 times even a small window is as big as most GPUs can
+/// draw. What the GPU in use can is checked once it is known.
+pub const MAX_SCREENSHOT_SCALE: u32 = 16;
+
+pub fn validate_screenshot_scale(s: &str) -> Result<(), String> {
+    validate_range(s, 1, MAX_SCREENSHOT_SCALE)
+}
+
+/// Within the bounds the camera keeps to while exploring
+pub fn validate_zoom(s: &str) -> Result<(), String> {
+    validate_range(s, camera::MIN_ZOOM, camera::MAX_ZOOM).map_err(|_| {
+        format!(
+            "expected a zoom between {:e} and {}, got '{}'",
+            camera::MIN_ZOOM,
+            camera::MAX_ZOOM,
+            s
+        )
+    })
+}
@@ -0,0 +1,52 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// How escaped points are colored, selected in the shaders by `Uniform::coloring`.
+/// Points inside the set are always black.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Coloring {
+    /// grey shading lit by the derivative, as if the set was embossed
+    Lighting = 0,
+    /// normalized iteration count `n + 1 - log2(log|z|)` through a palette
+    Smooth = 1,
+}
+
+const ALL: [Coloring; 2] = [Coloring::Lighting, Coloring::Smooth];
+
+impl Coloring {
+    pub fn from_u32(v: u32) -> Coloring {
+        ALL[v as usize % ALL.len()]
+    }
+
+    pub fn next(self) -> Coloring {
+        Coloring::from_u32(self as u32 + 1)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Coloring::Lighting => "lighting",
+            Coloring::Smooth => "smooth",
+        }
+    }
+}
+
+impl fmt::Display for Coloring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Coloring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Coloring, String> {
+        match ALL.iter().find(|c| c.name() == s) {
+            Some(c) => Ok(*c),
+            None => Err(format!(
+                "unknown coloring '{}', expected one of: {}",
+                s,
+                ALL.iter().map(|c| c.name()).collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+}
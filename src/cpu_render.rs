@@ -0,0 +1,417 @@
+use crate::coloring::Coloring;
+use crate::fractal::Formula;
+use crate::palette::{Palette, PALETTE_SIZE};
+use crate::perturbation::ReferenceOrbit;
+use crate::view::{Precision, View};
+use crate::vk_render::Uniform;
+use std::f32::consts::FRAC_1_SQRT_2;
+use std::sync::Mutex;
+use std::thread;
+
+// Straight port of `shaders/fractal.glsl`, so an image can be computed without any GPU.
+// Keep both in sync: every function here has a GLSL twin with the same name.
+
+fn squared_mod(vec: [f32; 2]) -> f32 {
+    vec[0] * vec[0] + vec[1] * vec[1]
+}
+
+fn c_mul(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[1] * b[0] + a[0] * b[1]]
+}
+
+fn c_pow(z: [f32; 2], n: u32) -> [f32; 2] {
+    let mut res = [1., 0.];
+    for _ in 0..n {
+        res = c_mul(res, z);
+    }
+    res
+}
+
+fn calc_d_inpc(uniform: &Uniform, d_inpc: [f32; 2], z: [f32; 2]) -> [f32; 2] {
+    match Formula::from_u32(uniform.formula) {
+        Formula::BurningShip => {
+            let flip_x = if z[0] < 0. { -1. } else { 1. };
+            let flip_y = if z[1] < 0. { -1. } else { 1. };
+            let d_inpc = [d_inpc[0] * flip_x * 2., d_inpc[1] * flip_y * 2.];
+            return c_mul(d_inpc, [z[0].abs(), z[1].abs()]);
+        }
+        Formula::Tricorn => {
+            let d_inpc = c_mul([d_inpc[0] * 2., d_inpc[1] * 2.], z);
+            return [d_inpc[0], -d_inpc[1]];
+        }
+        Formula::Multibrot => {
+            let n = uniform.power as f32;
+            return c_mul([d_inpc[0] * n, d_inpc[1] * n], c_pow(z, uniform.power - 1));
+        }
+        Formula::Mandelbrot => (),
+    }
+    let d_inpc = [d_inpc[0] * 2., d_inpc[1] * 2.];
+    [
+        d_inpc[0] * z[0] - d_inpc[1] * z[1],
+        d_inpc[1] * z[0] + d_inpc[0] * z[1],
+    ]
+}
+
+fn step_z(uniform: &Uniform, z: [f32; 2], c: [f32; 2]) -> [f32; 2] {
+    let formula = Formula::from_u32(uniform.formula);
+    let z = match formula {
+        Formula::BurningShip => [z[0].abs(), z[1].abs()],
+        Formula::Tricorn => [z[0], -z[1]],
+        _ => z,
+    };
+    if formula == Formula::Multibrot {
+        let zn = c_pow(z, uniform.power);
+        return [zn[0] + c[0], zn[1] + c[1]];
+    }
+    [
+        z[0] * z[0] - z[1] * z[1] + c[0],
+        z[1] * z[0] + z[0] * z[1] + c[1],
+    ]
+}
+
+/// `step_z_r` of the shader, like on devices with native doubles
+fn step_z_f64(uniform: &Uniform, z: [f64; 2], c: [f64; 2]) -> [f64; 2] {
+    let formula = Formula::from_u32(uniform.formula);
+    let z = match formula {
+        Formula::BurningShip => [z[0].abs(), z[1].abs()],
+        Formula::Tricorn => [z[0], -z[1]],
+        _ => z,
+    };
+    let mul = |a: [f64; 2], b: [f64; 2]| [a[0] * b[0] - a[1] * b[1], a[1] * b[0] + a[0] * b[1]];
+    let mut res = mul(z, z);
+    if formula == Formula::Multibrot {
+        res = z;
+        for _ in 1..uniform.power {
+            res = mul(res, z);
+        }
+    }
+    [res[0] + c[0], res[1] + c[1]]
+}
+
+fn diffabs(c: f32, d: f32) -> f32 {
+    if c >= 0. {
+        if c + d >= 0. {
+            d
+        } else {
+            -(2. * c + d)
+        }
+    } else if c + d > 0. {
+        2. * c + d
+    } else {
+        -d
+    }
+}
+
+fn perturb_z(uniform: &Uniform, ref_z: [f32; 2], dz: [f32; 2], dc: [f32; 2]) -> [f32; 2] {
+    let d = match Formula::from_u32(uniform.formula) {
+        Formula::BurningShip => [
+            (2. * ref_z[0] + dz[0]) * dz[0] - (2. * ref_z[1] + dz[1]) * dz[1],
+            2. * diffabs(
+                ref_z[0] * ref_z[1],
+                ref_z[0] * dz[1] + dz[0] * ref_z[1] + dz[0] * dz[1],
+            ),
+        ],
+        Formula::Multibrot => {
+            let z = [ref_z[0] + dz[0], ref_z[1] + dz[1]];
+            let mut sum = [1., 0.];
+            let mut ref_pow = [1., 0.];
+            for _ in 1..uniform.power {
+                ref_pow = c_mul(ref_pow, ref_z);
+                let s = c_mul(sum, z);
+                sum = [s[0] + ref_pow[0], s[1] + ref_pow[1]];
+            }
+            c_mul(dz, sum)
+        }
+        formula => {
+            let d = c_mul([2. * ref_z[0] + dz[0], 2. * ref_z[1] + dz[1]], dz);
+            if formula == Formula::Tricorn {
+                [d[0], -d[1]]
+            } else {
+                d
+            }
+        }
+    };
+    [d[0] + dc[0], d[1] + dc[1]]
+}
+
+fn c_div(c: [f32; 2], divi: [f32; 2]) -> [f32; 2] {
+    let div = (divi[0] * divi[0]) + (divi[1] * divi[1]);
+    [
+        ((c[0] * divi[0]) + (c[1] * divi[1])) / div,
+        ((c[1] * divi[0]) - (c[0] * divi[1])) / div,
+    ]
+}
+
+fn smooth_iter(uniform: &Uniform, i: u32, z: [f32; 2]) -> f32 {
+    let degree = if Formula::from_u32(uniform.formula) == Formula::Multibrot {
+        uniform.power as f32
+    } else {
+        2.
+    };
+    i as f32 + 1. - squared_mod(z).sqrt().ln().ln() / degree.ln()
+}
+
+fn palette_color(palette: &[[f32; 4]; PALETTE_SIZE], t: f32) -> [f32; 3] {
+    let t = (t - t.floor()) * PALETTE_SIZE as f32;
+    let a = t as usize % PALETTE_SIZE;
+    let b = (a + 1) % PALETTE_SIZE;
+    let f = t - t.floor();
+    let mix = |k: usize| palette[a][k] + (palette[b][k] - palette[a][k]) * f;
+    [mix(0), mix(1), mix(2)]
+}
+
+/// Computes the color (RGB, 0 to 1) of the point `pos` of the complex plane,
+/// like `main` of `shaders/fractal.glsl`.
+/// `delta` is `pos` - view center, `orbit` the points of the reference orbit, if any,
+/// and `palette` the texels of the palette.
+pub fn pixel_color(
+    uniform: &Uniform,
+    orbit: &[[f32; 2]],
+    palette: &[[f32; 4]; PALETTE_SIZE],
+    pos: [f32; 2],
+    delta: [f32; 2],
+) -> [f32; 3] {
+    let julia = uniform.julia != 0;
+    let dc = 0.0001;
+    let c = if julia {
+        [uniform.julia_x, uniform.julia_y]
+    } else {
+        pos
+    };
+    let mut z = pos;
+    let mut d_inpc = [1., 0.];
+    let mut dd_inpc = [dc, 0.];
+    let dd_step = if julia { 0. } else { dc };
+
+    let pos_r = [
+        f64::from(uniform.position_x) + f64::from(uniform.pos_x_lo) + f64::from(delta[0]),
+        f64::from(uniform.position_y) + f64::from(uniform.pos_y_lo) + f64::from(delta[1]),
+    ];
+    let c_r = if julia {
+        [f64::from(uniform.julia_x), f64::from(uniform.julia_y)]
+    } else {
+        pos_r
+    };
+    let mut z_r = pos_r;
+    let double = uniform.precision_mode == Precision::Double as u32;
+    if double {
+        z = [z_r[0] as f32, z_r[1] as f32];
+    }
+
+    let perturbation = uniform.precision_mode == Precision::Perturbation as u32;
+    let offset = [
+        delta[0] + uniform.ref_offset_x,
+        delta[1] + uniform.ref_offset_y,
+    ];
+    let delta_c = if julia { [0., 0.] } else { offset };
+    let mut dz = offset;
+    let mut m = if julia { 0 } else { 1 };
+    if perturbation {
+        z = [orbit[m][0] + dz[0], orbit[m][1] + dz[1]];
+    }
+
+    let mut i = 0;
+    while i < uniform.max_iter {
+        d_inpc = calc_d_inpc(uniform, d_inpc, z);
+        dd_inpc = calc_d_inpc(uniform, dd_inpc, z);
+        dd_inpc[0] += dd_step;
+        if perturbation {
+            // the orbit has no next point (the reference escaped): restart from its beginning
+            if m + 1 >= uniform.ref_len as usize {
+                dz = [z[0] - orbit[0][0], z[1] - orbit[0][1]];
+                m = 0;
+            }
+            dz = perturb_z(uniform, orbit[m], dz, delta_c);
+            m += 1;
+            z = [orbit[m][0] + dz[0], orbit[m][1] + dz[1]];
+            if !julia && squared_mod(z) < squared_mod(dz) {
+                dz = [z[0] - orbit[0][0], z[1] - orbit[0][1]];
+                m = 0;
+            }
+        } else if double {
+            z_r = step_z_f64(uniform, z_r, c_r);
+            z = [z_r[0] as f32, z_r[1] as f32];
+        } else {
+            z = step_z(uniform, z, c);
+        }
+        if squared_mod(d_inpc) < 0.0001 {
+            i = uniform.max_iter;
+            break;
+        }
+        if squared_mod(z) > 500. {
+            break;
+        }
+        i += 1;
+    }
+
+    let mut color = [0.; 3];
+    if i < uniform.max_iter && uniform.coloring == Coloring::Smooth as u32 {
+        let t = smooth_iter(uniform, i, z) / uniform.palette_period + uniform.palette_offset;
+        color = palette_color(palette, t);
+    } else if i < uniform.max_iter {
+        z = c_div(z, dd_inpc);
+        z = c_div(z, [z[0].abs(), z[1].abs()]);
+        let light = (z[0] * FRAC_1_SQRT_2 + z[1] * FRAC_1_SQRT_2 + 1.5) / 2.5;
+        color = [light.max(0.); 3];
+    }
+    color
+}
+
+/// Renders a `width` x `height` frame as tightly packed RGBA8 rows, top row first.
+/// Pixels are sampled at their center, the same way the rasterizer
+/// feeds the vertex shader outputs to the fragment shader.
+/// Rows are shared between all the cores.
+pub fn render(view: &View, palette: &Palette, width: u32, height: u32) -> Vec<u8> {
+    let reference = if view.precision() == Precision::Perturbation {
+        Some(ReferenceOrbit::compute(view))
+    } else {
+        None
+    };
+    let uniform = &view.uniform(reference.as_ref(), width as f32 / height as f32);
+    let (sin, cos) = uniform.rotation.sin_cos();
+    let orbit = reference.as_ref().map_or(&[][..], |r| &r.points[..]);
+    let palette = &palette.texels();
+
+    let render_row = |y: usize, row: &mut [u8]| {
+        let ndc_y = (y as f32 + 0.5) / height as f32 * 2. - 1.;
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            let ndc_x = (x as f32 + 0.5) / width as f32 * 2. - 1.;
+            let screen = [ndc_x * uniform.aspect, ndc_y];
+            let delta = [
+                (screen[0] * cos - screen[1] * sin) * uniform.zoom,
+                (screen[0] * sin + screen[1] * cos) * uniform.zoom,
+            ];
+            let pos = [delta[0] + uniform.position_x, delta[1] + uniform.position_y];
+            let color = pixel_color(uniform, orbit, palette, pos, delta);
+            for (out, channel) in pixel.iter_mut().zip(&color) {
+                *out = (channel.min(1.) * 255.).round() as u8;
+            }
+            pixel[3] = 255;
+        }
+    };
+
+    let mut pixels = vec![0; width as usize * height as usize * 4];
+    // handed out one at a time, as some rows take much longer than others
+    let rows = Mutex::new(pixels.chunks_mut(width as usize * 4).enumerate());
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let next = rows.lock().unwrap().next();
+                match next {
+                    Some((y, row)) => render_row(y, row),
+                    None => break,
+                }
+            });
+        }
+    });
+    pixels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::double_double::DoubleDouble;
+
+    fn view_at(x: f64, y: f64, zoom: f64) -> View {
+        View {
+            camera: Camera::new([DoubleDouble::from(x), DoubleDouble::from(y)], zoom),
+            ..View::default()
+        }
+    }
+
+    /// Color of the point `pos` of the complex plane in `view`, drawn with `precision`
+    fn color_at(view: &View, precision: Precision, pos: [f64; 2]) -> [f32; 3] {
+        let reference = ReferenceOrbit::compute(view);
+        let mut uniform = view.uniform(Some(&reference), 1.);
+        uniform.precision_mode = precision as u32;
+        let center = view.camera.center;
+        let delta = [
+            (pos[0] - center[0].hi - center[0].lo) as f32,
+            (pos[1] - center[1].hi - center[1].lo) as f32,
+        ];
+        let pos = [pos[0] as f32, pos[1] as f32];
+        let palette = Palette::default().texels();
+        pixel_color(&uniform, &reference.points, &palette, pos, delta)
+    }
+
+    #[test]
+    fn interior_points_are_black() {
+        let view = View::default();
+        // main cardioid, period 2 bulb, and near the cusp where it takes many iterations
+        for pos in [[0., 0.], [-0.2, 0.3], [-1., 0.], [0.24, 0.]] {
+            assert_eq!(
+                color_at(&view, Precision::Single, pos),
+                [0.; 3],
+                "{:?}",
+                pos
+            );
+        }
+    }
+
+    #[test]
+    fn escaping_points_take_the_palette() {
+        let view = View {
+            coloring: Coloring::Smooth,
+            ..View::default()
+        };
+        for pos in [[1., 1.], [-2.1, 0.], [0.3, -0.6]] {
+            let color = color_at(&view, Precision::Single, pos);
+            assert_ne!(color, [0.; 3], "{:?}", pos);
+            assert!(color.iter().all(|c| (0. ..=1.).contains(c)), "{:?}", color);
+        }
+    }
+
+    #[test]
+    fn lighting_shades_with_the_derivative() {
+        let view = View::default();
+        let lit = |pos| color_at(&view, Precision::Single, pos);
+        let colors: Vec<[f32; 3]> = [[0.3, 0.6], [0.3, -0.6], [-0.8, 0.25], [-0.8, -0.25]]
+            .iter()
+            .map(|&pos| lit(pos))
+            .collect();
+        for color in &colors {
+            assert!(color[0] == color[1] && color[1] == color[2], "{:?}", color);
+            assert!((0. ..=1.).contains(&color[0]), "{:?}", color);
+        }
+        // the light comes from one side, the mirror images of points across the real axis
+        // face it differently
+        assert_ne!(colors[0], colors[1]);
+        assert_ne!(colors[2], colors[3]);
+    }
+
+    #[test]
+    fn precisions_agree_at_a_shallow_zoom() {
+        let [x, y, zoom] = [-0.16, 1.04, 1e-3];
+        let view = View {
+            coloring: Coloring::Smooth,
+            max_iter: 200,
+            ..view_at(x, y, zoom)
+        };
+        let difference =
+            |a: [f32; 3], b: [f32; 3]| (0..3).map(|k| (a[k] - b[k]).abs()).fold(0., f32::max);
+        let mut single_close = 0;
+        for j in -8..8 {
+            for i in -8..8 {
+                let pos = [x + f64::from(i) * zoom / 8., y + f64::from(j) * zoom / 8.];
+                let double = color_at(&view, Precision::Double, pos);
+                let perturbation = color_at(&view, Precision::Perturbation, pos);
+                assert!(difference(double, perturbation) < 0.01, "{:?}", pos);
+                let single = color_at(&view, Precision::Single, pos);
+                single_close += (difference(double, single) < 0.02) as u32;
+            }
+        }
+        // f32 rounding may move the odd point near the boundary by an iteration
+        assert!(single_close >= 250, "{} of 256 points agree", single_close);
+    }
+
+    #[test]
+    fn perturbs_a_reference_that_escapes_at_once() {
+        let view = view_at(30., 0., 1e-12);
+        assert_eq!(view.precision(), Precision::Perturbation);
+        assert_eq!(ReferenceOrbit::compute(&view).points.len(), 2);
+        let pixels = render(&view, &Palette::default(), 4, 4);
+        assert_eq!(pixels.len(), 4 * 4 * 4);
+    }
+}
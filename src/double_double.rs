@@ -0,0 +1,338 @@
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// Unevaluated sum of two f64 (`hi` + `lo`, with |lo| <= ulp(hi) / 2),
+/// about 32 significant digits: enough to place the camera down to 1e-30 zooms
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DoubleDouble {
+    pub hi: f64,
+    pub lo: f64,
+}
+
+/// Significant digits written by `Display`
+const DISPLAY_DIGITS: usize = 33;
+
+/// a + b, exactly
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let bb = s - a;
+    (s, (a - (s - bb)) + (b - bb))
+}
+
+/// a + b, exactly, if |a| >= |b|
+fn quick_two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    (s, b - (s - a))
+}
+
+/// a * b, exactly
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    (p, a.mul_add(b, -p))
+}
+
+impl DoubleDouble {
+    pub fn to_f32(self) -> f32 {
+        self.hi as f32
+    }
+
+    /// [hi, lo] so that hi + lo is as close to self as two f32 allow
+    pub fn to_f32_pair(self) -> [f32; 2] {
+        let hi = self.hi as f32;
+        [hi, (self - f64::from(hi)).hi as f32]
+    }
+
+    pub fn abs(self) -> DoubleDouble {
+        if self.hi < 0. {
+            -self
+        } else {
+            self
+        }
+    }
+}
+
+impl From<f64> for DoubleDouble {
+    fn from(hi: f64) -> DoubleDouble {
+        DoubleDouble { hi, lo: 0. }
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn neg(self) -> DoubleDouble {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: DoubleDouble) -> DoubleDouble {
+        let (s, e) = two_sum(self.hi, other.hi);
+        let (t, f) = two_sum(self.lo, other.lo);
+        let (s, e) = quick_two_sum(s, e + t);
+        let (hi, lo) = quick_two_sum(s, e + f);
+        DoubleDouble { hi, lo }
+    }
+}
+
+impl Add<f64> for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: f64) -> DoubleDouble {
+        self + DoubleDouble::from(other)
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn sub(self, other: DoubleDouble) -> DoubleDouble {
+        self + -other
+    }
+}
+
+impl Sub<f64> for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn sub(self, other: f64) -> DoubleDouble {
+        self + DoubleDouble::from(-other)
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        let (p, e) = two_prod(self.hi, other.hi);
+        let e = e + (self.hi * other.lo + self.lo * other.hi);
+        let (hi, lo) = quick_two_sum(p, e);
+        DoubleDouble { hi, lo }
+    }
+}
+
+impl Mul<f64> for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: f64) -> DoubleDouble {
+        self * DoubleDouble::from(other)
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = DoubleDouble;
+
+    /// Long division, one f64 quotient digit at a time
+    fn div(self, other: DoubleDouble) -> DoubleDouble {
+        let q1 = self.hi / other.hi;
+        let r = self - other * q1;
+        let q2 = r.hi / other.hi;
+        let r = r - other * q2;
+        let q3 = r.hi / other.hi;
+        let (hi, lo) = quick_two_sum(q1, q2);
+        DoubleDouble { hi, lo } + q3
+    }
+}
+
+impl fmt::Display for DoubleDouble {
+    /// All the significant digits, so that parsing the output gives back the same number,
+    /// give or take the last bit of `lo`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.hi == 0. || !self.hi.is_finite() {
+            return write!(f, "{}", self.hi);
+        }
+        if self.hi < 0. {
+            f.write_str("-")?;
+        }
+        // bring the number in [1, 10)
+        let ten = DoubleDouble::from(10.);
+        let mut value = self.abs();
+        let mut exponent = 0;
+        while value.hi > 10. || (value.hi == 10. && value.lo >= 0.) {
+            value = value / ten;
+            exponent += 1;
+        }
+        while value.hi < 1. || (value.hi == 1. && value.lo < 0.) {
+            value = value * ten;
+            exponent -= 1;
+        }
+
+        // one digit more than we can trust, to round the last one
+        let mut digits = Vec::with_capacity(DISPLAY_DIGITS + 1);
+        for _ in 0..=DISPLAY_DIGITS {
+            let mut digit = value.hi.floor();
+            if digit == value.hi && value.lo < 0. {
+                digit -= 1.;
+            }
+            let digit = digit.clamp(0., 9.);
+            digits.push(digit as u8);
+            value = (value - digit) * ten;
+        }
+        let round_up = digits.pop().unwrap() >= 5;
+        if round_up {
+            let mut i = digits.len();
+            while i > 0 && digits[i - 1] == 9 {
+                digits[i - 1] = 0;
+                i -= 1;
+            }
+            if i == 0 {
+                // 9.99... rounded to 10
+                digits.insert(0, 1);
+                digits.pop();
+                exponent += 1;
+            } else {
+                digits[i - 1] += 1;
+            }
+        }
+        let digits: String = digits.iter().map(|d| (b'0' + d) as char).collect();
+        let digits = digits.trim_end_matches('0');
+
+        if (-5..=5).contains(&exponent) {
+            // plain decimal notation
+            if exponent < 0 {
+                write!(f, "0.{}{}", "0".repeat(-exponent as usize - 1), digits)
+            } else {
+                let int_len = exponent as usize + 1;
+                let padded = format!("{:0<width$}", digits, width = int_len);
+                let (int, frac) = padded.split_at(int_len);
+                if frac.is_empty() {
+                    f.write_str(int)
+                } else {
+                    write!(f, "{}.{}", int, frac)
+                }
+            }
+        } else {
+            let (first, rest) = digits.split_at(1);
+            if rest.is_empty() {
+                write!(f, "{}e{}", first, exponent)
+            } else {
+                write!(f, "{}.{}e{}", first, rest, exponent)
+            }
+        }
+    }
+}
+
+impl FromStr for DoubleDouble {
+    type Err = String;
+
+    /// Parses decimal numbers like `-0.7436438870371587047521915`, or `1.5e-20`,
+    /// without going through f64 so no digit is lost
+    fn from_str(s: &str) -> Result<DoubleDouble, String> {
+        let err = || format!("invalid number '{}'", s);
+        let (negative, rest) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (mantissa, exponent) = match rest.find(['e', 'E']) {
+            Some(i) => (&rest[..i], rest[i + 1..].parse::<i32>().map_err(|_| err())?),
+            None => (rest, 0),
+        };
+        // far past what a double can hold, and small enough for the digits to move it safely
+        if !(-400..=400).contains(&exponent) {
+            return Err(err());
+        }
+
+        let mut value = DoubleDouble::default();
+        let mut exponent = exponent;
+        let mut seen_dot = false;
+        let mut digits = 0;
+        for c in mantissa.chars() {
+            match c {
+                '.' if !seen_dot => seen_dot = true,
+                '0'..='9' => {
+                    // digits beyond what we can store only move the decimal point
+                    if digits < 40 {
+                        value = value * 10. + f64::from(c as u8 - b'0');
+                        if value.hi != 0. {
+                            digits += 1;
+                        }
+                        if seen_dot {
+                            exponent -= 1;
+                        }
+                    } else if !seen_dot {
+                        exponent += 1;
+                    }
+                }
+                _ => return Err(err()),
+            }
+        }
+        if mantissa.is_empty() || mantissa == "." {
+            return Err(err());
+        }
+
+        if exponent.abs() > 300 {
+            return Err(err());
+        }
+        let ten = DoubleDouble::from(10.);
+        for _ in 0..exponent.max(0) {
+            value = value * ten;
+        }
+        let mut scale = DoubleDouble::from(1.);
+        for _ in 0..(-exponent).max(0) {
+            scale = scale * ten;
+        }
+        value = value / scale;
+        if !value.hi.is_finite() {
+            return Err(err());
+        }
+        Ok(if negative { -value } else { value })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_more_digits_than_f64() {
+        let x: DoubleDouble = "-0.7436438870371587047521915".parse().unwrap();
+        assert_eq!(x.hi, -0.7436438870371587);
+        assert!(x.lo != 0.);
+        let y: DoubleDouble = "1.5e-20".parse().unwrap();
+        assert!((y.hi - 1.5e-20).abs() < 1e-35);
+        assert_eq!("+2".parse::<DoubleDouble>(), Ok(DoubleDouble::from(2.)));
+        for s in ["", ".", "-", "1.2.3", "1e", "0x10", "1,5", "inf", "NaN"] {
+            assert!(s.parse::<DoubleDouble>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let third = DoubleDouble::from(1.) / DoubleDouble::from(3.);
+        for x in [
+            third,
+            -third * DoubleDouble::from(1e-15),
+            DoubleDouble::from(-1.25),
+            DoubleDouble::from(0.),
+            "123456789.123456789123456789".parse().unwrap(),
+            "-0.7436438870371587047521915".parse().unwrap(),
+        ] {
+            let parsed: DoubleDouble = x.to_string().parse().unwrap();
+            assert!(
+                (parsed - x).abs().hi <= x.abs().hi * 1e-30,
+                "{} read back as {}",
+                x,
+                parsed
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_exponents_out_of_range() {
+        for s in [
+            "1e-2147483648",
+            "1e2147483647",
+            "1e401",
+            "0.5e-401",
+            "1e999999999999",
+        ] {
+            assert!(s.parse::<DoubleDouble>().is_err(), "{}", s);
+        }
+        assert!("1e-300".parse::<DoubleDouble>().is_ok());
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Iterated function, selected in the shaders by `Uniform::formula`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Formula {
+    /// z^2 + c
+    Mandelbrot = 0,
+    /// (|re z| + i|im z|)^2 + c
+    BurningShip = 1,
+    /// conj(z)^2 + c
+    Tricorn = 2,
+    /// z^n + c, n being `Uniform::power`
+    Multibrot = 3,
+}
+
+pub const MIN_POWER: u32 = 2;
+pub const MAX_POWER: u32 = 16;
+
+const ALL: [Formula; 4] = [
+    Formula::Mandelbrot,
+    Formula::BurningShip,
+    Formula::Tricorn,
+    Formula::Multibrot,
+];
+
+impl Formula {
+    pub fn from_u32(v: u32) -> Formula {
+        ALL[v as usize % ALL.len()]
+    }
+
+    pub fn next(self) -> Formula {
+        Formula::from_u32(self as u32 + 1)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Formula::Mandelbrot => "mandelbrot",
+            Formula::BurningShip => "burning-ship",
+            Formula::Tricorn => "tricorn",
+            Formula::Multibrot => "multibrot",
+        }
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Formula {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Formula, String> {
+        match ALL.iter().find(|f| f.name() == s) {
+            Some(f) => Ok(*f),
+            None => Err(format!(
+                "unknown formula '{}', expected one of: {}",
+                s,
+                ALL.iter().map(|f| f.name()).collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+}
@@ -1,8 +1,20 @@
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, AtomicU32, Ordering::*};
-use std::sync::Arc;
+use std::sync::{mpsc, Arc};
+use winit::dpi::{LogicalPosition, LogicalSize};
 use winit::VirtualKeyCode as KeyCode;
-use winit::{ControlFlow, Event, WindowEvent};
+use winit::{ControlFlow, Event, MouseButton, MouseScrollDelta, WindowEvent};
 
+use crate::tour::Tour;
+
+/// Zoom factor of one mouse wheel notch
+const WHEEL_ZOOM: f64 = 0.8;
+/// Zoom factor of one pixel of touchpad scrolling
+const PIXEL_ZOOM: f64 = 0.995;
+
+#[allow(clippy::upper_case_acronyms)]
 pub enum BTKey {
     UP = 0b1,
     DWN = 0b10,
@@ -10,6 +22,47 @@ pub enum BTKey {
     RGT = 0b1000,
     W = 0b1_0000,
     S = 0b10_0000,
+    I = 0b100_0000,
+    K = 0b1000_0000,
+    J = 0b1_0000_0000,
+    L = 0b10_0000_0000,
+    Q = 0b100_0000_0000,
+    E = 0b1000_0000_0000,
+}
+
+/// One-shot commands, sent to the physics thread once per key press or mouse event
+pub enum Action {
+    /// move the camera center by this screen vector
+    Pan([f64; 2]),
+    /// multiply the zoom by .1, keeping the point at .0 (screen coordinates) in place
+    ZoomAt([f64; 2], f64),
+    ToggleJulia,
+    NextFormula,
+    PowerUp,
+    PowerDown,
+    MoreIterations,
+    FewerIterations,
+    ToggleAutoIterations,
+    NextColoring,
+    PaletteForward,
+    PaletteBackward,
+    PaletteStretch,
+    PaletteSqueeze,
+    TogglePaletteCycling,
+    /// save the current location under this name
+    SaveBookmark(String),
+    /// jump to the nth bookmark (0 based)
+    RecallBookmark(usize),
+    /// save the view as a PNG, at this multiple of the window resolution
+    Screenshot(u32),
+    /// play a `Tour` from its start, driving the camera until it ends
+    PlayTour(Tour),
+    /// stop the tour playing, or replay the last one
+    ToggleTour,
+    /// go to the location of a location file or of a PNG saved by fractol, or play a tour file
+    Open(PathBuf),
+    /// show or hide the coordinates, zoom, iterations and frame rate
+    ToggleHud,
 }
 
 pub struct PKeys {
@@ -32,17 +85,37 @@ impl PKeys {
         self.p_keys.store(new_pkeys, Relaxed);
     }
 
+    pub fn clear(&self) {
+        self.p_keys.store(0, Relaxed);
+    }
+
     pub fn contains(&self, key: BTKey) -> bool {
         (self.p_keys.load(Relaxed) & (key as u32)) != 0
     }
 }
 
+/// Screen coordinates (see `camera::Camera`) of a cursor position
+fn to_screen(cursor: LogicalPosition, window: LogicalSize) -> [f64; 2] {
+    [
+        (2. * cursor.x - window.width) / window.height,
+        2. * cursor.y / window.height - 1.,
+    ]
+}
+
 pub fn input_loop(
     mut events_loop: winit::EventsLoop,
+    mut window_size: LogicalSize,
     recreate_swapchain: Arc<AtomicBool>,
     exit: Arc<AtomicBool>,
     p_keys: Arc<PKeys>,
+    actions: mpsc::Sender<Action>,
+    screenshot_scale: u32,
 ) {
+    let mut held = HashSet::new(); // to ignore key repeats
+    let mut cursor = LogicalPosition::new(0., 0.);
+    let mut dragging = false;
+    // Some while typing the name of a new bookmark
+    let mut bookmark_name: Option<String> = None;
     events_loop.run_forever(|ev| {
         match ev {
             Event::WindowEvent {
@@ -50,31 +123,176 @@ pub fn input_loop(
                 ..
             } => exit.store(true, Relaxed),
             Event::WindowEvent {
-                event: WindowEvent::Resized(_),
+                event: WindowEvent::DroppedFile(path),
                 ..
-            } => recreate_swapchain.store(true, Relaxed),
+            } => {
+                actions.send(Action::Open(path)).ok();
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Focused(false),
+                ..
+            } => {
+                // the releases will go to another window
+                dragging = false;
+                held.clear();
+                p_keys.clear();
+            }
+            Event::WindowEvent {
+                event: WindowEvent::Resized(size),
+                ..
+            } => {
+                window_size = size;
+                recreate_swapchain.store(true, Relaxed);
+            }
 
             Event::WindowEvent {
-                event: WindowEvent::KeyboardInput { input, .. },
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                if dragging {
+                    // the point under the cursor follows it
+                    let (from, to) = (
+                        to_screen(cursor, window_size),
+                        to_screen(position, window_size),
+                    );
+                    actions
+                        .send(Action::Pan([from[0] - to[0], from[1] - to[1]]))
+                        .ok();
+                }
+                cursor = position;
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        button: MouseButton::Left,
+                        state,
+                        ..
+                    },
+                ..
+            } => dragging = state == winit::ElementState::Pressed,
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
                 ..
             } => {
-                let key = input.virtual_keycode.unwrap();
-                let fn_ptr = match input.state {
-                    winit::ElementState::Pressed => PKeys::add,
-                    winit::ElementState::Released => PKeys::rm,
+                let factor = match delta {
+                    MouseScrollDelta::LineDelta(_, lines) => WHEEL_ZOOM.powf(f64::from(lines)),
+                    MouseScrollDelta::PixelDelta(pixels) => PIXEL_ZOOM.powf(pixels.y),
                 };
+                actions
+                    .send(Action::ZoomAt(to_screen(cursor, window_size), factor))
+                    .ok();
+            }
 
-                match key {
-                    KeyCode::W => fn_ptr(&p_keys, BTKey::W),
-                    KeyCode::S => fn_ptr(&p_keys, BTKey::S),
-                    KeyCode::Left => fn_ptr(&p_keys, BTKey::LFT),
-                    KeyCode::Right => fn_ptr(&p_keys, BTKey::RGT),
-                    KeyCode::Up => fn_ptr(&p_keys, BTKey::UP),
-                    KeyCode::Down => fn_ptr(&p_keys, BTKey::DWN),
-                    KeyCode::Escape => exit.store(true, Relaxed),
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(c),
+                ..
+            } => {
+                if let Some(name) = &mut bookmark_name {
+                    if !c.is_control() {
+                        name.push(c);
+                        print!("{}", c);
+                        std::io::stdout().flush().ok();
+                    }
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput { input, .. },
+                ..
+            } if bookmark_name.is_some() && input.state == winit::ElementState::Pressed => {
+                match input.virtual_keycode {
+                    Some(KeyCode::Return) => {
+                        println!();
+                        let name = bookmark_name.take().unwrap();
+                        actions.send(Action::SaveBookmark(name)).ok();
+                    }
+                    Some(KeyCode::Escape) => {
+                        println!(" (cancelled)");
+                        bookmark_name = None;
+                    }
+                    Some(KeyCode::Back) => {
+                        let name = bookmark_name.as_mut().unwrap();
+                        if name.pop().is_some() {
+                            print!("\u{8} \u{8}");
+                            std::io::stdout().flush().ok();
+                        }
+                    }
                     _ => (),
                 }
             }
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput { input, .. },
+                ..
+            } => {
+                if let Some(key) = input.virtual_keycode {
+                    let fn_ptr = match input.state {
+                        winit::ElementState::Pressed => PKeys::add,
+                        winit::ElementState::Released => PKeys::rm,
+                    };
+                    let first_press = match input.state {
+                        winit::ElementState::Pressed => held.insert(key),
+                        winit::ElementState::Released => {
+                            held.remove(&key);
+                            false
+                        }
+                    };
+                    let send = |action| {
+                        if first_press {
+                            actions.send(action).ok();
+                        }
+                    };
+
+                    match key {
+                        KeyCode::W => fn_ptr(&p_keys, BTKey::W),
+                        KeyCode::S => fn_ptr(&p_keys, BTKey::S),
+                        KeyCode::Left => fn_ptr(&p_keys, BTKey::LFT),
+                        KeyCode::Right => fn_ptr(&p_keys, BTKey::RGT),
+                        KeyCode::Up => fn_ptr(&p_keys, BTKey::UP),
+                        KeyCode::Down => fn_ptr(&p_keys, BTKey::DWN),
+                        KeyCode::I => fn_ptr(&p_keys, BTKey::I),
+                        KeyCode::K => fn_ptr(&p_keys, BTKey::K),
+                        KeyCode::J => fn_ptr(&p_keys, BTKey::J),
+                        KeyCode::L => fn_ptr(&p_keys, BTKey::L),
+                        KeyCode::Q => fn_ptr(&p_keys, BTKey::Q),
+                        KeyCode::E => fn_ptr(&p_keys, BTKey::E),
+                        KeyCode::Tab => send(Action::ToggleJulia),
+                        KeyCode::F => send(Action::NextFormula),
+                        KeyCode::PageUp => send(Action::PowerUp),
+                        KeyCode::PageDown => send(Action::PowerDown),
+                        KeyCode::Equals | KeyCode::Add => send(Action::MoreIterations),
+                        KeyCode::Minus | KeyCode::Subtract => send(Action::FewerIterations),
+                        KeyCode::A => send(Action::ToggleAutoIterations),
+                        KeyCode::C => send(Action::NextColoring),
+                        KeyCode::Period => send(Action::PaletteForward),
+                        KeyCode::Comma => send(Action::PaletteBackward),
+                        KeyCode::RBracket => send(Action::PaletteStretch),
+                        KeyCode::LBracket => send(Action::PaletteSqueeze),
+                        KeyCode::P => send(Action::TogglePaletteCycling),
+                        KeyCode::T => send(Action::ToggleTour),
+                        KeyCode::H => send(Action::ToggleHud),
+                        KeyCode::B if first_press && input.modifiers.ctrl => {
+                            print!("Bookmark name (Enter to save, Escape to cancel): ");
+                            std::io::stdout().flush().ok();
+                            bookmark_name = Some(String::new());
+                        }
+                        KeyCode::F12 if input.modifiers.shift => {
+                            send(Action::Screenshot(screenshot_scale))
+                        }
+                        KeyCode::F12 => send(Action::Screenshot(1)),
+                        KeyCode::Key1 | KeyCode::Numpad1 => send(Action::RecallBookmark(0)),
+                        KeyCode::Key2 | KeyCode::Numpad2 => send(Action::RecallBookmark(1)),
+                        KeyCode::Key3 | KeyCode::Numpad3 => send(Action::RecallBookmark(2)),
+                        KeyCode::Key4 | KeyCode::Numpad4 => send(Action::RecallBookmark(3)),
+                        KeyCode::Key5 | KeyCode::Numpad5 => send(Action::RecallBookmark(4)),
+                        KeyCode::Key6 | KeyCode::Numpad6 => send(Action::RecallBookmark(5)),
+                        KeyCode::Key7 | KeyCode::Numpad7 => send(Action::RecallBookmark(6)),
+                        KeyCode::Key8 | KeyCode::Numpad8 => send(Action::RecallBookmark(7)),
+                        KeyCode::Key9 | KeyCode::Numpad9 => send(Action::RecallBookmark(8)),
+                        // only on press, the release may end the typing of a bookmark name
+                        KeyCode::Escape if first_press => exit.store(true, Relaxed),
+                        _ => (),
+                    }
+                }
+            }
             _ => (),
         };
         if exit.load(Relaxed) {
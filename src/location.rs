@@ -0,0 +1,266 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::camera::Camera;
+use crate::cli;
+use crate::double_double::DoubleDouble;
+use crate::fractal;
+use crate::palette;
+use crate::png_io;
+use crate::view::{self, View};
+
+/// A place worth coming back to: everything that defines the image, saved as
+/// human-editable `key = value` lines:
+/// ```text
+/// center = -0.7436438870371587047521915, 0.1318259042053119704931
+/// zoom = 1e-12
+/// formula = mandelbrot
+/// iterations = 2000
+/// palette = /home/me/palettes/fire.map
+/// ```
+/// Missing keys keep the value of `View::default()`.
+#[derive(Clone)]
+pub struct Location {
+    pub view: View,
+    /// gradient file of the palette, `None` for the default one
+    pub palette: Option<PathBuf>,
+}
+
+fn parse<T: FromStr>(value: &str) -> Option<T> {
+    value.trim().parse().ok()
+}
+
+fn parse_pair<T: FromStr>(value: &str) -> Option<[T; 2]> {
+    let mut it = value.splitn(2, ',');
+    Some([parse(it.next()?)?, parse(it.next()?)?])
+}
+
+/// `Camera::rotation` of an angle in degrees
+pub fn radians(degrees: f64) -> f32 {
+    degrees.to_radians() as f32
+}
+
+/// The shortest number of degrees that `radians` reads back as the same rotation,
+/// so that saving and reopening a location does not drift
+fn degrees(radians: f32) -> String {
+    let degrees = f64::from(radians).to_degrees();
+    (0..17)
+        .map(|decimals| format!("{:.*}", decimals, degrees))
+        .find(|s| s.parse().map(self::radians) == Ok(radians))
+        .unwrap_or_else(|| degrees.to_string())
+}
+
+impl Location {
+    /// `lines` being numbered for the error messages
+    pub fn parse<'a>(lines: impl Iterator<Item = (usize, &'a str)>) -> Result<Location, String> {
+        let base = Location {
+            view: View::default(),
+            palette: None,
+        };
+        base.parse_changes(lines)
+    }
+
+    /// Like `parse`, but the missing keys keep their value in `self`
+    pub fn parse_changes<'a>(
+        &self,
+        lines: impl Iterator<Item = (usize, &'a str)>,
+    ) -> Result<Location, String> {
+        let mut view = self.view;
+        let mut palette = self.palette.clone();
+        let mut rotation = None;
+        for (n, line) in lines {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |what: &str| format!("line {}: {} in '{}'", n + 1, what, line);
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return Err(error("expected 'key = value'")),
+            };
+            // the same checks as the command line options
+            let checked = match key {
+                "zoom" => cli::validate_zoom(value),
+                "rotation" => cli::validate_finite::<f64>(value),
+                "julia" => cli::validate_finite_pair::<f32>(value),
+                "palette_offset" => cli::validate_finite::<f32>(value),
+                _ => Ok(()),
+            };
+            if let Err(e) = checked {
+                return Err(format!("line {}: {}: {}", n + 1, key, e));
+            }
+            let parsed = match key {
+                "center" => parse_pair::<DoubleDouble>(value).map(|c| view.camera.center = c),
+                "zoom" => parse(value).map(|z| view.camera.zoom = z),
+                "rotation" => parse(value).map(|r| rotation = Some(radians(r))),
+                "julia" => parse_pair(value).map(|c| {
+                    view.julia = true;
+                    view.julia_c = c;
+                }),
+                "formula" => parse(value).map(|f| view.formula = f),
+                "power" => parse(value)
+                    .filter(|p| (fractal::MIN_POWER..=fractal::MAX_POWER).contains(p))
+                    .map(|p| view.power = p),
+                "iterations" => parse(value)
+                    .filter(|i| (view::MIN_ITERATIONS..=view::MAX_ITERATIONS).contains(i))
+                    .map(|i| view.max_iter = i),
+                "auto_iterations" => parse(value).map(|a| view.auto_iter = a),
+                "coloring" => parse(value).map(|c| view.coloring = c),
+                "palette_offset" => parse(value).map(|o| view.palette_offset = o),
+                "palette_period" => parse(value)
+                    .filter(|p| (palette::MIN_PERIOD..=palette::MAX_PERIOD).contains(p))
+                    .map(|p| view.palette_period = p),
+                "palette" => {
+                    palette = Some(PathBuf::from(value));
+                    Some(())
+                }
+                _ => return Err(error("unknown key")),
+            };
+            if parsed.is_none() {
+                return Err(error("invalid value"));
+            }
+        }
+        view.camera = Camera {
+            rotation: rotation.unwrap_or(view.camera.rotation),
+            ..Camera::new(view.camera.center, view.camera.zoom)
+        };
+        Ok(Location { view, palette })
+    }
+
+    /// Reads a location file, or the location saved in a PNG written by fractol
+    pub fn load(path: &Path) -> Result<Location, String> {
+        if path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("png"))
+        {
+            return png_io::read_location(path).map_err(|e| format!("{}: {}", path.display(), e));
+        }
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Location::parse(text.lines().enumerate()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let view = &self.view;
+        let camera = &view.camera;
+        writeln!(f, "center = {}, {}", camera.center[0], camera.center[1])?;
+        writeln!(f, "zoom = {:e}", camera.zoom)?;
+        writeln!(f, "rotation = {}", degrees(camera.rotation))?;
+        if view.julia {
+            writeln!(f, "julia = {}, {}", view.julia_c[0], view.julia_c[1])?;
+        }
+        writeln!(f, "formula = {}", view.formula)?;
+        writeln!(f, "power = {}", view.power)?;
+        writeln!(f, "iterations = {}", view.max_iter)?;
+        writeln!(f, "auto_iterations = {}", view.auto_iter)?;
+        writeln!(f, "coloring = {}", view.coloring)?;
+        writeln!(f, "palette_offset = {}", view.palette_offset)?;
+        writeln!(f, "palette_period = {}", view.palette_period)?;
+        if let Some(palette) = &self.palette {
+            writeln!(f, "palette = {}", palette.display())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coloring::Coloring;
+    use crate::fractal::Formula;
+
+    fn parse_text(text: &str) -> Result<Location, String> {
+        Location::parse(text.lines().enumerate())
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let mut view = View {
+            julia: true,
+            julia_c: [-0.8, 0.156],
+            formula: Formula::Multibrot,
+            power: 4,
+            max_iter: 2000,
+            auto_iter: true,
+            coloring: Coloring::Smooth,
+            palette_offset: 0.25,
+            palette_period: 80.,
+            ..View::default()
+        };
+        view.camera = Camera {
+            rotation: radians(33.3),
+            ..Camera::new(
+                [
+                    "-0.7436438870371587047521915".parse().unwrap(),
+                    "0.1318259042053119704931".parse().unwrap(),
+                ],
+                1.5e-12,
+            )
+        };
+        let location = Location {
+            view,
+            palette: Some(PathBuf::from("palettes/fire.map")),
+        };
+        let parsed = parse_text(&location.to_string()).unwrap();
+        let (a, b) = (&location.view, &parsed.view);
+        for (x, y) in a.camera.center.iter().zip(&b.camera.center) {
+            assert!(
+                (*x - *y).abs().hi <= x.abs().hi * 1e-30,
+                "{} read back as {}",
+                x,
+                y
+            );
+        }
+        assert_eq!(a.camera.zoom, b.camera.zoom);
+        assert_eq!(a.camera.rotation, b.camera.rotation);
+        assert_eq!((a.julia, a.julia_c), (b.julia, b.julia_c));
+        assert_eq!((a.formula, a.power), (b.formula, b.power));
+        assert_eq!((a.max_iter, a.auto_iter), (b.max_iter, b.auto_iter));
+        assert_eq!(a.coloring, b.coloring);
+        assert_eq!(
+            (a.palette_offset, a.palette_period),
+            (b.palette_offset, b.palette_period)
+        );
+        assert_eq!(location.palette, parsed.palette);
+    }
+
+    #[test]
+    fn missing_keys_keep_their_value() {
+        let location = parse_text("# the seahorses\n\nzoom = 5e-2\n").unwrap();
+        let default = View::default();
+        assert_eq!(location.view.camera.zoom, 5e-2);
+        assert_eq!(location.view.camera.center, default.camera.center);
+        assert_eq!(location.view.max_iter, default.max_iter);
+        assert_eq!(location.palette, None);
+
+        let changed = location
+            .parse_changes("iterations = 500".lines().enumerate())
+            .unwrap();
+        assert_eq!(changed.view.camera.zoom, 5e-2);
+        assert_eq!(changed.view.max_iter, 500);
+    }
+
+    #[test]
+    fn reports_the_line_of_errors() {
+        for (text, error) in [
+            ("zoom = 1\ncolour = smooth", "line 2: unknown key"),
+            ("zoom = -1", "line 1: zoom:"),
+            ("\nzoom = inf", "line 2: zoom:"),
+            ("zoom = 1e300", "line 1: zoom:"),
+            ("rotation = nan", "line 1: rotation:"),
+            ("palette_offset = inf", "line 1: palette_offset:"),
+            ("julia = nan, 0", "line 1: julia:"),
+            ("\npower = 99", "line 2: invalid value"),
+            ("center = 1", "line 1: invalid value"),
+            ("iterations", "line 1: expected 'key = value'"),
+        ] {
+            match parse_text(text) {
+                Ok(_) => panic!("'{}' parsed", text),
+                Err(e) => assert!(e.starts_with(error), "'{}' gave '{}'", text, e),
+            }
+        }
+    }
+}
@@ -3,147 +3,287 @@ extern crate vulkano_win;
 #[macro_use]
 extern crate vulkano;
 extern crate bitfield;
+extern crate clap;
+extern crate png;
 extern crate winit;
 
+mod backend;
+mod batch;
+mod bookmarks;
+mod camera;
+mod cli;
+mod coloring;
+mod cpu_render;
+mod double_double;
+mod fractal;
 mod input;
+mod location;
 mod movement;
+mod palette;
+mod perturbation;
+mod png_io;
+mod poster;
+mod tour;
+mod triple_buffer;
+mod video;
+mod view;
 mod vk_render;
 
-use std::sync::{Arc, Mutex};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+use std::sync::{mpsc, Arc};
 use std::thread;
 
 use input::*;
 use movement::game_loop;
+use tour::Tour;
+use vulkano::swapchain::PresentMode;
 
 fn main() {
+    let args = clap::App::new("fractol")
+        .about("Mandelbrot explorer")
+        .args(&cli::view_args())
+        .arg(
+            clap::Arg::with_name("window-size")
+                .long("window-size")
+                .value_name("WIDTHxHEIGHT")
+                .help("Size of the window, in logical pixels")
+                .validator(|v| cli::validate_size(&v)),
+        )
+        .arg(
+            clap::Arg::with_name("fullscreen")
+                .long("fullscreen")
+                .help("Opens the window fullscreen on the primary monitor"),
+        )
+        .arg(
+            clap::Arg::with_name("present-mode")
+                .long("present-mode")
+                .value_name("MODE")
+                .help("Vulkan present mode, fifo being vsync")
+                .possible_values(&["fifo", "mailbox", "immediate", "relaxed"])
+                .default_value("fifo"),
+        )
+        .arg(
+            clap::Arg::with_name("tour")
+                .long("tour")
+                .value_name("FILE")
+                .help("Plays a tour of keyframes once the window opens"),
+        )
+        .arg(
+            clap::Arg::with_name("screenshot-scale")
+                .long("screenshot-scale")
+                .value_name("N")
+                .help("Resolution of the Shift + F12 screenshots, in multiples of the window's")
+                .default_value("4")
+                .validator(|v| cli::validate_screenshot_scale(&v)),
+        )
+        .arg(
+            clap::Arg::with_name("frame-stats")
+                .long("frame-stats")
+                .help(
+                "Prints statistics of the frame, GPU, acquire and present times every 5 seconds",
+            ),
+        )
+        .arg(
+            clap::Arg::with_name("frame-csv")
+                .long("frame-csv")
+                .value_name("FILE")
+                .help(
+                    "Writes the frame, GPU, acquire and present times of every frame to a CSV file",
+                ),
+        )
+        .arg(cli::device_arg())
+        .arg(
+            clap::Arg::with_name("list-devices")
+                .long("list-devices")
+                .help("Lists the Vulkan devices, numbered for --device"),
+        )
+        .subcommand(batch::subcommand())
+        .subcommand(poster::subcommand())
+        .subcommand(video::subcommand())
+        .get_matches();
+    if args.is_present("list-devices") {
+        if let Err(e) = vk_render::device::list() {
+            eprintln!("Could not list the devices: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    if let Some(render_args) = args.subcommand_matches("render") {
+        batch::render(render_args);
+        return;
+    }
+    if let Some(poster_args) = args.subcommand_matches("poster") {
+        poster::render(poster_args);
+        return;
+    }
+    if let Some(video_args) = args.subcommand_matches("video") {
+        video::render(video_args);
+        return;
+    }
+
+    let mut location = cli::location(&args).unwrap_or_else(|e| {
+        eprintln!("Could not read the location: {}", e);
+        process::exit(1);
+    });
+    let palette = cli::load_palette(&args, &mut location.palette).unwrap_or_else(|e| {
+        eprintln!("Could not load the palette {}", e);
+        process::exit(1);
+    });
+    let tour = args.value_of("tour").map(|path| {
+        Tour::load(Path::new(path)).unwrap_or_else(|e| {
+            eprintln!("Could not read the tour {}", e);
+            process::exit(1);
+        })
+    });
+    let window = vk_render::WindowOptions {
+        size: args
+            .value_of("window-size")
+            .map(|size| cli::parse_pair(size, 'x')),
+        fullscreen: args.is_present("fullscreen"),
+        present_mode: match args.value_of("present-mode").unwrap() {
+            "mailbox" => PresentMode::Mailbox,
+            "immediate" => PresentMode::Immediate,
+            "relaxed" => PresentMode::Relaxed,
+            _ => PresentMode::Fifo,
+        },
+    };
+
     let events_loop = winit::EventsLoop::new();
     let pressed_keys = Arc::new(PKeys::new());
 
-    let mut vk = vk_render::Graphics::new(&events_loop);
+    let mut vk = vk_render::Graphics::new(&events_loop, &palette, &window, &cli::device(&args))
+        .unwrap_or_else(|e| render_failed(e));
+    let screenshot_scale = cli::parse(args.value_of("screenshot-scale").unwrap());
+    if screenshot_scale > vk.max_screenshot_scale() {
+        eprintln!(
+            "Could not use a screenshot scale of {}: the GPU draws at most {} times the window",
+            screenshot_scale,
+            vk.max_screenshot_scale()
+        );
+        process::exit(1);
+    }
+    if args.is_present("frame-stats") {
+        vk.stats.report();
+    }
+    if let Some(path) = args.value_of("frame-csv") {
+        vk.stats.write_csv(Path::new(path)).unwrap_or_else(|e| {
+            eprintln!("Could not create the frame times file {}", e);
+            process::exit(1);
+        });
+    }
     let exit = vk.exit.clone();
     let rs = vk.recreate_swapchain.clone();
+    let window_size = vk.surface.window().get_inner_size().unwrap_or_else(|| {
+        render_failed(vk_render::RenderError::Window(
+            "the window closed".to_owned(),
+        ))
+    });
 
-    let zoom = 0.5;
-    let pos_x = -1.;
-    let pos_y = 0.;
-
-    let uniform = Arc::new(Mutex::new(vk_render::Uniform {
-        zoom,
-        position_x: pos_x,
-        position_y: pos_y,
-    }));
+    let view = location.view;
+    // written by the physics thread, read by the render thread
+    let (view_writer, view_reader) = triple_buffer::triple_buffer(view);
+    let (actions_tx, actions_rx) = mpsc::channel();
+    let (commands_tx, commands_rx) = mpsc::channel();
+    if let Some(tour) = tour {
+        actions_tx.send(Action::PlayTour(tour)).ok();
+    }
 
-    let u = uniform.clone();
-    thread::spawn(move || vk.loop_render(u));
+    let stop_input = StopInput {
+        exit: exit.clone(),
+        wake: events_loop.create_proxy(),
+    };
+    let render = thread::spawn(move || {
+        let _stop_input = stop_input;
+        vk.loop_render(view_reader, commands_rx)
+    });
 
     let e = exit.clone();
     let pk = pressed_keys.clone();
-    let u = uniform.clone();
-    thread::spawn(move || game_loop(e, pk, u));
+    let palette_path = location.palette;
+    let physics = thread::spawn(move || {
+        game_loop(
+            e,
+            pk,
+            actions_rx,
+            view,
+            view_writer,
+            palette_path,
+            commands_tx,
+        )
+    });
+
+    input::input_loop(
+        events_loop,
+        window_size,
+        rs,
+        exit.clone(),
+        pressed_keys.clone(),
+        actions_tx,
+        screenshot_scale,
+    );
+    // lets them finish writing the bookmarks and the frame times
+    physics.join().ok();
+    match render.join() {
+        Ok(Ok(())) => (),
+        Ok(Err(e)) => render_failed(e),
+        // its panic message is already printed
+        Err(_) => process::exit(101),
+    }
+}
+
+/// Stops the input loop when dropped by the render thread, however it ends
+struct StopInput {
+    exit: Arc<AtomicBool>,
+    /// wakes the input loop up, so that it sees `exit`
+    wake: winit::EventsLoopProxy,
+}
+
+impl Drop for StopInput {
+    fn drop(&mut self) {
+        self.exit.store(true, Relaxed);
+        self.wake.wakeup().ok();
+    }
+}
 
-    input::input_loop(events_loop, rs, exit.clone(), pressed_keys.clone());
+/// Explains why the GPU can't draw and exits with the code of the error
+fn render_failed(e: vk_render::RenderError) -> ! {
+    eprintln!("{}\n{}", e, e.hint());
+    process::exit(e.exit_code());
 }
 
 mod vs {
     vulkano_shaders::shader! {
         ty: "vertex",
-        src: "
-#version 450
-
-layout(location = 0) in vec2 position;
-layout(binding = 0) uniform Data {
-    float zoom;
-    float pos_x;
-    float pos_y;
-} uniforms;
-
-layout(location = 0) out vec2 pos;
-
-void main() {
-    gl_Position = vec4(position, 0.0, 1.0);
-    pos = position * uniforms.zoom;
-    pos.x += uniforms.pos_x;
-    pos.y += uniforms.pos_y;
-}"
+        path: "src/shaders/fractal.vert"
     }
 }
 
 mod fs {
     vulkano_shaders::shader! {
         ty: "fragment",
-        src: "
-#version 450
-
-layout(location = 0) in vec2 pos;
-
-layout(location = 0) out vec4 f_color;
-
-float squared_mod(vec2 vec)
-{
-    return (vec.x * vec.x + vec.y * vec.y);
-}
-
-vec2 calc_d_inpc(vec2 d_inpc, vec2 z)
-{
-    d_inpc = d_inpc * 2;
-    d_inpc = vec2(
-        d_inpc.x * z.x - d_inpc.y * z.y,
-        d_inpc.y * z.x + d_inpc.x * z.y
-    );
-    return (d_inpc);
+        path: "src/shaders/fractal.frag"
+    }
 }
 
-vec2	c_div(vec2 c, vec2 divi)
-{
-	float	re;
-
-	re = c.x;
-	c.x = ((c.x * divi.x) + (c.y * divi.y))
-			/ ((divi.x * divi.x) + (divi.y * divi.y));
-	c.y = ((c.y * divi.x) - (re * divi.y))
-			/ ((divi.x * divi.x) + (divi.y * divi.y));
-	return (c);
+mod fs_double {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/fractal_double.frag"
+    }
 }
 
-void main() {
-    float dc = 0.0001;
-    vec2 c = pos;
-    vec2 z = c;
-    vec2 d_inpc = vec2(1, 0);
-    vec2 dd_inpc = vec2(dc, 0);
-    float sqrmod_z;
-
-    float i;
-    for(i = 0; i < 1.; i += 0.01) {
-        d_inpc = calc_d_inpc(d_inpc, z);
-        dd_inpc = calc_d_inpc(dd_inpc, z) + vec2(dc, 0);
-        z = vec2(
-            z.x * z.x - z.y * z.y + c.x,
-            z.y * z.x + z.x * z.y + c.y
-        );
-        if (squared_mod(d_inpc) < 0.0001)
-        {
-            i = 1.;
-			break ;
-		}
-        if (squared_mod(z) > 500)
-			break ;
+mod hud_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        path: "src/shaders/hud.vert"
     }
+}
 
-    float color;
-    color = 0.;
-	if (i < 0.99)
-	{
-        z = c_div(z, dd_inpc);
-        z = c_div(z, abs(z));
-        z.x = (z.x * 0.7071067811865475 + z.y * 0.7071067811865475 + 1.5) / 2.5;
-        if (z.x < 0)
-            z.x = 0;
-        color = z.x;
-        // color=1.;
-	}
-
-    f_color = vec4(vec3(color), 1.0);
-}"
+mod hud_fs {
+    vulkano_shaders::shader! {
+        ty: "fragment",
+        path: "src/shaders/hud.frag"
     }
 }
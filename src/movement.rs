@@ -1,53 +1,290 @@
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering::*};
-use std::sync::{Arc, Mutex};
+use std::sync::{mpsc, Arc};
 use std::{thread, time};
 
+use crate::bookmarks::Bookmarks;
+use crate::camera::{Camera, CameraInput};
+use crate::double_double::DoubleDouble;
+use crate::fractal;
 use crate::input::*;
-use crate::vk_render::*;
+use crate::location::Location;
+use crate::palette::{self, Palette};
+use crate::tour::Tour;
+use crate::triple_buffer::Writer;
+use crate::view::{self, View};
+use crate::vk_render::RenderCommand;
 
 const PHYSICS_TIME: u64 = 5; // 5ms <=> 200Hz
+const JULIA_SPEED: f32 = 0.1; // per second
+const PALETTE_STEP: f32 = 1. / 16.;
+const PALETTE_CYCLING: f32 = 0.1; // palettes per second
 
-pub fn game_loop(exit: Arc<AtomicBool>, p_keys: Arc<PKeys>, uniform: Arc<Mutex<Uniform>>) {
-    let mut zoom = 0.5;
-    let mut pos_x = -1.;
-    let mut pos_y = 0.;
+pub fn game_loop(
+    exit: Arc<AtomicBool>,
+    p_keys: Arc<PKeys>,
+    actions: mpsc::Receiver<Action>,
+    mut view: View,
+    mut shared_view: Writer<View>,
+    mut palette_path: Option<PathBuf>,
+    commands: mpsc::Sender<RenderCommand>,
+) {
+    // camera of the mode we are not in, restored when toggling back
+    let mut other_camera = Camera::new([DoubleDouble::from(0.); 2], 1.5);
+    // the last tour played, and how far into it we are while it plays
+    let mut tour: Option<Tour> = None;
+    let mut tour_time: Option<f64> = None;
+    // read once, only written afterwards, and away from this thread so that it never waits on the disk
+    let mut bookmarks = Bookmarks::load();
+    let (bookmark_saves, bookmark_saver) = bookmark_saver();
+    let mut last_tick = time::Instant::now();
     loop {
         let now = time::Instant::now();
+        // movements follow the real time, however late this thread gets
+        let dt = now.duration_since(last_tick).as_secs_f64();
+        last_tick = now;
         if exit.load(Relaxed) {
-            return;
+            break;
         }
-        if p_keys.contains(BTKey::W) {
-            zoom /= 1.10;
-        }
-        if p_keys.contains(BTKey::S) && zoom < 2. {
-            zoom *= 1.10;
-        }
-        if p_keys.contains(BTKey::LFT) {
-            pos_x -= 0.05 * zoom;
+        for action in actions.try_iter() {
+            match action {
+                Action::Pan(v) => view.camera.pan(v),
+                Action::ZoomAt(screen, factor) => view.camera.zoom_at(screen, factor),
+                Action::ToggleJulia => {
+                    if !view.julia {
+                        // explore the julia set of the point at the center of the screen
+                        let center = view.camera.center;
+                        view.julia_c = [center[0].to_f32(), center[1].to_f32()];
+                    }
+                    view.julia = !view.julia;
+                    std::mem::swap(&mut view.camera, &mut other_camera);
+                }
+                Action::NextFormula => {
+                    view.formula = view.formula.next();
+                    println!("Formula: {}", view.formula);
+                }
+                Action::PowerUp if view.power < fractal::MAX_POWER => {
+                    view.power += 1;
+                    println!("Multibrot power: {}", view.power);
+                }
+                Action::PowerDown if view.power > fractal::MIN_POWER => {
+                    view.power -= 1;
+                    println!("Multibrot power: {}", view.power);
+                }
+                Action::PowerUp | Action::PowerDown => (),
+                Action::MoreIterations => {
+                    view.max_iter = (view.max_iter * 5 / 4).min(view::MAX_ITERATIONS);
+                    println!("Iterations: {}", view.max_iter);
+                }
+                Action::FewerIterations => {
+                    view.max_iter = (view.max_iter * 4 / 5).max(view::MIN_ITERATIONS);
+                    println!("Iterations: {}", view.max_iter);
+                }
+                Action::ToggleAutoIterations => {
+                    view.auto_iter = !view.auto_iter;
+                    println!("Automatic iterations: {}", view.auto_iter);
+                }
+                Action::NextColoring => {
+                    view.coloring = view.coloring.next();
+                    println!("Coloring: {}", view.coloring);
+                }
+                Action::PaletteForward => {
+                    view.palette_offset = (view.palette_offset + PALETTE_STEP).fract()
+                }
+                Action::PaletteBackward => {
+                    view.palette_offset = (view.palette_offset - PALETTE_STEP).rem_euclid(1.)
+                }
+                Action::PaletteStretch => {
+                    view.palette_period = (view.palette_period * 1.25).min(palette::MAX_PERIOD);
+                    println!("Palette period: {} iterations", view.palette_period);
+                }
+                Action::PaletteSqueeze => {
+                    view.palette_period = (view.palette_period / 1.25).max(palette::MIN_PERIOD);
+                    println!("Palette period: {} iterations", view.palette_period);
+                }
+                Action::TogglePaletteCycling => view.palette_cycling = !view.palette_cycling,
+                Action::SaveBookmark(name) => {
+                    let location = Location {
+                        view,
+                        palette: palette_path.clone(),
+                    };
+                    let inserted =
+                        bookmarks
+                            .as_mut()
+                            .map_err(|e| e.clone())
+                            .and_then(|bookmarks| {
+                                bookmarks.insert(&name, location)?;
+                                Ok(bookmarks.clone())
+                            });
+                    match inserted {
+                        Ok(bookmarks) => {
+                            bookmark_saves
+                                .send((name.trim().to_owned(), bookmarks))
+                                .ok();
+                        }
+                        Err(e) => eprintln!("Could not save the bookmark: {}", e),
+                    }
+                }
+                Action::RecallBookmark(n) => match &bookmarks {
+                    Ok(bookmarks) => match bookmarks.entries.get(n) {
+                        Some((name, location)) => {
+                            println!("Bookmark {}: {}", n + 1, name);
+                            go_to(location.clone(), &mut view, &mut palette_path, &commands);
+                        }
+                        None => println!("No bookmark {}", n + 1),
+                    },
+                    Err(e) => eprintln!("Could not read the bookmarks: {}", e),
+                },
+                Action::PlayTour(new_tour) => {
+                    start_tour(&new_tour, &mut view, &mut palette_path, &commands);
+                    tour = Some(new_tour);
+                    tour_time = Some(0.);
+                }
+                Action::ToggleTour => match &tour {
+                    Some(_) if tour_time.is_some() => {
+                        println!("Tour stopped");
+                        tour_time = None;
+                    }
+                    Some(tour) => {
+                        start_tour(tour, &mut view, &mut palette_path, &commands);
+                        tour_time = Some(0.);
+                    }
+                    None => {
+                        println!("No tour, open one with --tour or by dropping it on the window")
+                    }
+                },
+                Action::Open(path) if path.extension().is_some_and(|e| e == "tour") => {
+                    match Tour::load(&path) {
+                        Ok(new_tour) => {
+                            start_tour(&new_tour, &mut view, &mut palette_path, &commands);
+                            tour = Some(new_tour);
+                            tour_time = Some(0.);
+                        }
+                        Err(e) => eprintln!("Could not open {}", e),
+                    }
+                }
+                Action::Open(path) => match Location::load(&path) {
+                    Ok(location) => {
+                        println!("Opened {}", path.display());
+                        go_to(location, &mut view, &mut palette_path, &commands);
+                    }
+                    Err(e) => eprintln!("Could not open {}", e),
+                },
+                Action::Screenshot(scale) => {
+                    let location = Location {
+                        view,
+                        palette: palette_path.clone(),
+                    };
+                    commands
+                        .send(RenderCommand::Screenshot { location, scale })
+                        .ok();
+                }
+                Action::ToggleHud => {
+                    commands.send(RenderCommand::ToggleHud).ok();
+                }
+            }
         }
-        if p_keys.contains(BTKey::RGT) {
-            pos_x += 0.05 * zoom;
+        let axis = |less: BTKey, more: BTKey| {
+            f64::from(p_keys.contains(more) as u8) - f64::from(p_keys.contains(less) as u8)
+        };
+        let input = CameraInput {
+            pan: [axis(BTKey::LFT, BTKey::RGT), axis(BTKey::UP, BTKey::DWN)],
+            zoom: axis(BTKey::S, BTKey::W),
+            rotate: axis(BTKey::Q, BTKey::E),
+        };
+        match (&tour, tour_time) {
+            (Some(tour), Some(time)) => {
+                let time = time + dt;
+                view = View {
+                    palette_cycling: view.palette_cycling,
+                    ..tour.view_at(time)
+                };
+                tour_time = Some(time).filter(|&time| time < tour.duration());
+                if tour_time.is_none() {
+                    println!("Tour finished");
+                }
+            }
+            _ => view.camera.step(dt, &input),
         }
-        if p_keys.contains(BTKey::UP) {
-            pos_y -= 0.05 * zoom;
+        if view.palette_cycling {
+            view.palette_offset = (view.palette_offset + PALETTE_CYCLING * dt as f32).fract();
         }
-        if p_keys.contains(BTKey::DWN) {
-            pos_y += 0.05 * zoom;
+        if view.julia && tour_time.is_none() {
+            let step = JULIA_SPEED * dt as f32;
+            view.julia_c[0] += axis(BTKey::J, BTKey::L) as f32 * step;
+            view.julia_c[1] += axis(BTKey::I, BTKey::K) as f32 * step;
         }
 
-        let mut u = uniform.lock().unwrap(); // This lock here is causing some bad delays :/
-        u.zoom = zoom;
-        u.position_x = pos_x;
-        u.position_y = pos_y;
-        drop(u); // otherwise mutex is not unlocked
-
-        let sleep_dur = match time::Duration::from_millis(PHYSICS_TIME).checked_sub(now.elapsed()) {
-            Some(t) => t,
-            None => {
-                println!("Physics can't keep up !");
-                time::Duration::from_secs(0)
+        shared_view.write(view);
+
+        let sleep_dur = time::Duration::from_millis(PHYSICS_TIME).checked_sub(now.elapsed());
+        thread::sleep(sleep_dur.unwrap_or_default());
+    }
+    // lets the last bookmarks be written before the process ends
+    drop(bookmark_saves);
+    bookmark_saver.join().ok();
+}
+
+/// Writes the bookmarks it is sent, on a thread of its own, in the order they were sent.
+/// They come with the name of the bookmark just saved. The thread ends once the sender is dropped
+fn bookmark_saver() -> (mpsc::Sender<(String, Bookmarks)>, thread::JoinHandle<()>) {
+    let (saves, to_save) = mpsc::channel::<(String, Bookmarks)>();
+    let saver = thread::spawn(move || {
+        for (name, bookmarks) in to_save {
+            match bookmarks.save() {
+                Ok(()) => println!("Saved bookmark '{}'", name),
+                Err(e) => eprintln!("Could not save the bookmark: {}", e),
             }
-        };
-        thread::sleep(sleep_dur);
+        }
+    });
+    (saves, saver)
+}
+
+/// Goes to the first key of `tour`
+fn start_tour(
+    tour: &Tour,
+    view: &mut View,
+    palette_path: &mut Option<PathBuf>,
+    commands: &mpsc::Sender<RenderCommand>,
+) {
+    println!("Playing a {}s tour, T to stop it", tour.duration());
+    let location = Location {
+        view: tour.view_at(0.),
+        palette: tour.palette.clone(),
+    };
+    go_to(location, view, palette_path, commands);
+}
+
+/// Replaces the view and the palette by those of `location`
+fn go_to(
+    location: Location,
+    view: &mut View,
+    palette_path: &mut Option<PathBuf>,
+    commands: &mpsc::Sender<RenderCommand>,
+) {
+    // the path only changes with the palette on screen, so that what is saved is what is seen
+    if location.palette != *palette_path && switch_palette(&location.palette, commands) {
+        *palette_path = location.palette;
     }
+    *view = View {
+        palette_cycling: view.palette_cycling,
+        ..location.view
+    };
+}
+
+/// Sends the palette of the gradient file at `path` to the render thread,
+/// false if it could not be loaded and the current one stays
+fn switch_palette(path: &Option<PathBuf>, commands: &mpsc::Sender<RenderCommand>) -> bool {
+    let palette = match path {
+        Some(path) => match Palette::load(path) {
+            Ok(palette) => palette,
+            Err(e) => {
+                eprintln!("Could not load the palette {}", e);
+                return false;
+            }
+        },
+        None => Palette::default(),
+    };
+    commands.send(RenderCommand::Palette(palette)).ok();
+    true
 }
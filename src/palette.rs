@@ -0,0 +1,304 @@
+use std::fs;
+use std::path::Path;
+
+/// Number of colors uploaded to the shaders, must match `PALETTE_SIZE` in `shaders/fractal.glsl`
+pub const PALETTE_SIZE: usize = 256;
+
+/// Bounds of `View::palette_period`
+pub const MIN_PERIOD: f32 = 1.;
+pub const MAX_PERIOD: f32 = 10_000.;
+
+/// A color of the gradient, at `position` (0 to 1) along it
+#[derive(Clone, Copy, Debug)]
+pub struct Stop {
+    pub position: f32,
+    pub color: [f32; 3],
+}
+
+/// Cyclic color gradient, linearly interpolated between its stops.
+/// After the last stop it blends back into the first one.
+#[derive(Clone, Debug)]
+pub struct Palette {
+    stops: Vec<Stop>,
+}
+
+impl Default for Palette {
+    /// Deep blue, white, orange, black
+    fn default() -> Palette {
+        let stop = |position, r: u8, g: u8, b: u8| Stop {
+            position,
+            color: [r as f32 / 255., g as f32 / 255., b as f32 / 255.],
+        };
+        Palette {
+            stops: vec![
+                stop(0., 0, 7, 100),
+                stop(0.16, 32, 107, 203),
+                stop(0.42, 237, 255, 255),
+                stop(0.6425, 255, 170, 0),
+                stop(0.8575, 0, 2, 0),
+            ],
+        }
+    }
+}
+
+impl Palette {
+    /// Stops may be given in any order, positions must be between 0 and 1
+    pub fn from_stops(mut stops: Vec<Stop>) -> Result<Palette, String> {
+        if stops.is_empty() {
+            return Err("a palette needs at least one color".to_owned());
+        }
+        if let Some(s) = stops.iter().find(|s| !(0. ..=1.).contains(&s.position)) {
+            return Err(format!(
+                "stop position {} is not between 0 and 1",
+                s.position
+            ));
+        }
+        stops.sort_by(|a, b| a.position.partial_cmp(&b.position).unwrap());
+        Ok(Palette { stops })
+    }
+
+    /// Reads a gradient file, its format picked from the extension:
+    /// - `.map`: Fractint palette, one `RED GREEN BLUE` color per line, evenly spaced
+    /// - `.ugr`: UltraFractal gradients, only the first one of the file is used
+    /// - anything else: one stop per line, `POSITION RED GREEN BLUE`, the position
+    ///   between 0 and 1 and the color components between 0 and 255.
+    ///   Empty lines and lines starting with `#` are ignored.
+    pub fn load(path: &Path) -> Result<Palette, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        // old palette files are often latin-1, only their comments are not ascii
+        let text = String::from_utf8_lossy(&bytes);
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        let stops = match extension.as_deref() {
+            Some("map") => parse_map(&text),
+            Some("ugr") => parse_ugr(&text),
+            _ => parse_gradient(&text),
+        };
+        stops
+            .and_then(Palette::from_stops)
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Color at `t`, wrapping around every 1
+    pub fn sample(&self, t: f32) -> [f32; 3] {
+        let t = t - t.floor();
+        let next = self
+            .stops
+            .iter()
+            .position(|s| s.position > t)
+            .unwrap_or(self.stops.len());
+        let a = self.stops[(next + self.stops.len() - 1) % self.stops.len()];
+        let b = self.stops[next % self.stops.len()];
+        // distances along the loop, so the segment from the last stop to the first one works too
+        let span = (b.position - a.position).rem_euclid(1.);
+        let f = if span > 0. {
+            (t - a.position).rem_euclid(1.) / span
+        } else {
+            0.
+        };
+        [
+            a.color[0] + (b.color[0] - a.color[0]) * f,
+            a.color[1] + (b.color[1] - a.color[1]) * f,
+            a.color[2] + (b.color[2] - a.color[2]) * f,
+        ]
+    }
+
+    /// The gradient sampled `PALETTE_SIZE` times, as sent to the shaders
+    pub fn texels(&self) -> [[f32; 4]; PALETTE_SIZE] {
+        let mut texels = [[0., 0., 0., 1.]; PALETTE_SIZE];
+        for (i, texel) in texels.iter_mut().enumerate() {
+            let [r, g, b] = self.sample(i as f32 / PALETTE_SIZE as f32);
+            *texel = [r, g, b, 1.];
+        }
+        texels
+    }
+}
+
+/// Parse error of `line`, `n` being its 0 based index
+fn line_error(n: usize, expected: &str, line: &str) -> String {
+    format!("line {}: expected {}, got '{}'", n + 1, expected, line)
+}
+
+/// A color component, 0 to 255
+fn parse_component(s: &str) -> Option<f32> {
+    let v: f32 = s.parse().ok()?;
+    if (0. ..=255.).contains(&v) {
+        Some(v / 255.)
+    } else {
+        None
+    }
+}
+
+fn parse_gradient(text: &str) -> Result<Vec<Stop>, String> {
+    let mut stops = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parse = || {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 4 {
+                return None;
+            }
+            Some(Stop {
+                position: fields[0].parse().ok()?,
+                color: [
+                    parse_component(fields[1])?,
+                    parse_component(fields[2])?,
+                    parse_component(fields[3])?,
+                ],
+            })
+        };
+        stops.push(parse().ok_or_else(|| line_error(n, "'POSITION RED GREEN BLUE'", line))?);
+    }
+    Ok(stops)
+}
+
+/// Fractint `.map`: usually 256 lines of `RED GREEN BLUE`, optionally followed by a comment
+fn parse_map(text: &str) -> Result<Vec<Stop>, String> {
+    let mut colors = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let parse = || {
+            let mut fields = line.split_whitespace();
+            Some([
+                parse_component(fields.next()?)?,
+                parse_component(fields.next()?)?,
+                parse_component(fields.next()?)?,
+            ])
+        };
+        colors.push(parse().ok_or_else(|| line_error(n, "'RED GREEN BLUE'", line))?);
+    }
+    let len = colors.len() as f32;
+    Ok(colors
+        .into_iter()
+        .enumerate()
+        .map(|(i, color)| Stop {
+            position: i as f32 / len,
+            color,
+        })
+        .collect())
+}
+
+/// UltraFractal `.ugr`, a list of named gradients like:
+/// ```text
+/// name {
+/// gradient:
+///   title="name" smooth=yes
+///   index=0 color=16777215
+///   index=200 color=8421376
+/// opacity:
+///   ...
+/// }
+/// ```
+/// Indexes go from 0 to 399, colors are `0xBBGGRR` in decimal.
+fn parse_ugr(text: &str) -> Result<Vec<Stop>, String> {
+    let mut stops = Vec::new();
+    let mut in_gradient = false;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        match line {
+            "gradient:" => in_gradient = true,
+            // end of the section, or of the first gradient
+            "}" | "opacity:" if in_gradient => break,
+            _ if in_gradient && line.starts_with("index=") => {
+                let field = |name: &str| {
+                    line.split_whitespace()
+                        .find_map(|f| f.strip_prefix(name))
+                        .and_then(|v| v.parse::<i64>().ok())
+                };
+                let (index, color) = match (field("index="), field("color=")) {
+                    (Some(i), Some(c)) => (i, c),
+                    _ => return Err(line_error(n, "'index=N color=BGR'", line)),
+                };
+                let component = |shift: u32| ((color >> shift) & 0xff) as f32 / 255.;
+                stops.push(Stop {
+                    // indexes can be out of range, ultrafractal wraps them
+                    position: index.rem_euclid(400) as f32 / 400.,
+                    color: [component(0), component(8), component(16)],
+                });
+            }
+            _ => (),
+        }
+    }
+    if stops.is_empty() {
+        return Err("no gradient found".to_owned());
+    }
+    Ok(stops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colors(stops: &[Stop]) -> Vec<(f32, [f32; 3])> {
+        stops.iter().map(|s| (s.position, s.color)).collect()
+    }
+
+    #[test]
+    fn parses_fractint_maps() {
+        let stops = parse_map("255 0 0  red\n0 255 0\n\n0 0 255 blue, an old comment\n0 0 0\n");
+        assert_eq!(
+            colors(&stops.unwrap()),
+            [
+                (0., [1., 0., 0.]),
+                (0.25, [0., 1., 0.]),
+                (0.5, [0., 0., 1.]),
+                (0.75, [0., 0., 0.]),
+            ]
+        );
+        assert!(parse_map("255 0\n").unwrap_err().starts_with("line 1:"));
+        assert!(parse_map("0 0 0\n256 0 0\n")
+            .unwrap_err()
+            .starts_with("line 2:"));
+    }
+
+    #[test]
+    fn parses_the_first_ultrafractal_gradient() {
+        let text = "fire {\n\
+                    gradient:\n\
+                    \x20 title=\"fire\" smooth=yes\n\
+                    \x20 index=0 color=255\n\
+                    \x20 index=200 color=65280\n\
+                    \x20 index=-100 color=16711680\n\
+                    opacity:\n\
+                    \x20 index=0 opacity=255\n\
+                    }\n\
+                    ice {\n\
+                    gradient:\n\
+                    \x20 index=0 color=0\n\
+                    }\n";
+        assert_eq!(
+            colors(&parse_ugr(text).unwrap()),
+            [
+                (0., [1., 0., 0.]),
+                (0.5, [0., 1., 0.]),
+                (0.75, [0., 0., 1.]),
+            ]
+        );
+        assert!(parse_ugr("fire {\n}\n").is_err());
+        assert!(parse_ugr("gradient:\nindex=3 colour=1\n")
+            .unwrap_err()
+            .starts_with("line 2:"));
+    }
+
+    #[test]
+    fn parses_stops_in_any_order() {
+        let stops = parse_gradient("# sunset\n1 0 0 255\n\n0 255 0 0\n0.5 0 255 0\n").unwrap();
+        let palette = Palette::from_stops(stops).unwrap();
+        assert_eq!(
+            colors(&palette.stops),
+            [(0., [1., 0., 0.]), (0.5, [0., 1., 0.]), (1., [0., 0., 1.])]
+        );
+        assert_eq!(palette.sample(0.25), [0.5, 0.5, 0.]);
+        assert!(parse_gradient("0.5 0 255\n").is_err());
+        assert!(Palette::from_stops(Vec::new()).is_err());
+        assert!(Palette::from_stops(parse_gradient("1.5 0 0 0").unwrap()).is_err());
+    }
+}
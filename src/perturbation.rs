@@ -0,0 +1,102 @@
+use crate::double_double::DoubleDouble;
+use crate::fractal::Formula;
+use crate::view::View;
+
+/// Orbit of the view center computed in high precision, the shaders only iterate
+/// the (small) difference between each pixel and this orbit, which f32 handles fine.
+/// For the Mandelbrot set `points[0]` is 0 and `points[1]` the center,
+/// for Julia sets `points[0]` is the center.
+pub struct ReferenceOrbit {
+    pub center: [DoubleDouble; 2],
+    pub points: Vec<[f32; 2]>,
+    // parameters the orbit was computed for
+    julia: bool,
+    julia_c: [f32; 2],
+    formula: Formula,
+    power: u32,
+    iterations: u32,
+}
+
+fn step(
+    z: [DoubleDouble; 2],
+    c: [DoubleDouble; 2],
+    formula: Formula,
+    power: u32,
+) -> [DoubleDouble; 2] {
+    let z = match formula {
+        Formula::BurningShip => [z[0].abs(), z[1].abs()],
+        Formula::Tricorn => [z[0], -z[1]],
+        _ => z,
+    };
+    if formula == Formula::Multibrot {
+        let mut res = [DoubleDouble::from(1.), DoubleDouble::default()];
+        for _ in 0..power {
+            res = [res[0] * z[0] - res[1] * z[1], res[1] * z[0] + res[0] * z[1]];
+        }
+        return [res[0] + c[0], res[1] + c[1]];
+    }
+    [z[0] * z[0] - z[1] * z[1] + c[0], z[0] * z[1] * 2. + c[1]]
+}
+
+impl ReferenceOrbit {
+    pub fn compute(view: &View) -> ReferenceOrbit {
+        let (mut z, c) = if view.julia {
+            let c = [
+                DoubleDouble::from(f64::from(view.julia_c[0])),
+                DoubleDouble::from(f64::from(view.julia_c[1])),
+            ];
+            (view.camera.center, c)
+        } else {
+            ([DoubleDouble::default(); 2], view.camera.center)
+        };
+
+        let iterations = view.iterations();
+        let mut points = Vec::with_capacity(iterations as usize + 2);
+        points.push([z[0].to_f32(), z[1].to_f32()]);
+        for _ in 0..=iterations {
+            z = step(z, c, view.formula, view.power);
+            let point = [z[0].to_f32(), z[1].to_f32()];
+            points.push(point);
+            if point[0] * point[0] + point[1] * point[1] > 500. {
+                break;
+            }
+        }
+
+        ReferenceOrbit {
+            center: view.camera.center,
+            points,
+            julia: view.julia,
+            julia_c: view.julia_c,
+            formula: view.formula,
+            power: view.power,
+            iterations,
+        }
+    }
+
+    /// Distance from the reference to the view center, in the complex plane
+    pub fn offset(&self, view: &View) -> [f32; 2] {
+        [
+            (view.camera.center[0] - self.center[0]).to_f32(),
+            (view.camera.center[1] - self.center[1]).to_f32(),
+        ]
+    }
+
+    /// Whether the reference left the set before the last iteration
+    fn escaped(&self) -> bool {
+        self.points.len() < self.iterations as usize + 2
+    }
+
+    /// Whether this orbit can still be used to draw `view`.
+    /// The reference does not have to be the view center, but once it is out of the screen
+    /// the pixel offsets get large compared to the pixel size and f32 loses the details.
+    pub fn fits(&self, view: &View) -> bool {
+        let offset = self.offset(view);
+        self.julia == view.julia
+            && self.julia_c == view.julia_c
+            && self.formula == view.formula
+            && self.power == view.power
+            && (self.escaped() || self.iterations >= view.iterations())
+            && f64::from(offset[0].abs()) <= view.camera.zoom
+            && f64::from(offset[1].abs()) <= view.camera.zoom
+    }
+}
@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::location::Location;
+
+/// Prefix of the keywords of the text chunks holding the location, one chunk per key
+const KEYWORD_PREFIX: &str = "fractol:";
+
+/// Writes tightly packed RGBA8 rows (top row first) to a PNG file, along with
+/// the `location` they show, so that the viewer can reopen it
+pub fn write_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    location: &Location,
+) -> Result<(), png::EncodingError> {
+    encode_png(File::create(path)?, width, height, rgba, location)
+}
+
+/// `write_png` to an already open file
+pub fn encode_png(
+    file: File,
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    location: &Location,
+) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    add_location(&mut encoder, location)?;
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()
+}
+
+/// Stores the `key = value` lines of `location` in tEXt chunks
+pub fn add_location<W: std::io::Write>(
+    encoder: &mut png::Encoder<W>,
+    location: &Location,
+) -> Result<(), png::EncodingError> {
+    for line in location.to_string().lines() {
+        if let Some((key, value)) = line.split_once(" = ") {
+            let keyword = format!("{}{}", KEYWORD_PREFIX, key);
+            // tEXt is Latin-1, a palette path may need iTXt's UTF-8
+            if value.chars().all(|c| (c as u32) < 0x100) {
+                encoder.add_text_chunk(keyword, value.to_owned())?;
+            } else {
+                encoder.add_itxt_chunk(keyword, value.to_owned())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The location saved by `write_png`
+pub fn read_location(path: &Path) -> Result<Location, String> {
+    let file = File::open(path).map_err(|e| e.to_string())?;
+    let reader = png::Decoder::new(file)
+        .read_info()
+        .map_err(|e| e.to_string())?;
+    let info = reader.info();
+    let mut lines = Vec::new();
+    for chunk in &info.uncompressed_latin1_text {
+        lines.push((chunk.keyword.clone(), chunk.text.clone()));
+    }
+    for chunk in &info.utf8_text {
+        lines.push((
+            chunk.keyword.clone(),
+            chunk.get_text().map_err(|e| e.to_string())?,
+        ));
+    }
+    let lines: Vec<String> = lines
+        .into_iter()
+        .filter_map(|(keyword, text)| {
+            let key = keyword.strip_prefix(KEYWORD_PREFIX)?;
+            Some(format!("{} = {}", key, text))
+        })
+        .collect();
+    if lines.is_empty() {
+        return Err("no location saved in this image".to_owned());
+    }
+    Location::parse(lines.iter().map(String::as_str).enumerate())
+}
@@ -0,0 +1,216 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Instant;
+
+use crate::backend::Backend;
+use crate::cli;
+use crate::location::Location;
+use crate::palette::Palette;
+use crate::png_io;
+use crate::view::View;
+
+/// Renders images too big to fit in memory, for printing.
+/// The image is cut in bands of one row of tiles: each band is rendered tile by tile
+/// and saved next to the output, so that an interrupted render picks up where it
+/// stopped. Once all of them are there, they are streamed into the output one by one.
+pub fn render(args: &clap::ArgMatches) {
+    let mut location = cli::location(args).unwrap_or_else(|e| {
+        eprintln!("Could not read the location: {}", e);
+        process::exit(1);
+    });
+    let palette = cli::load_palette(args, &mut location.palette).unwrap_or_else(|e| {
+        eprintln!("Could not load the palette {}", e);
+        process::exit(1);
+    });
+    let poster = Poster {
+        size: cli::parse_pair(args.value_of("size").unwrap(), 'x'),
+        tile: cli::parse(args.value_of("tile").unwrap()),
+        location,
+        output: PathBuf::from(args.value_of("output").unwrap()),
+    };
+    let mut backend = Backend::new(args.is_present("cpu"), &cli::device(args), &palette);
+    if let Err(e) = poster.render(&mut backend, &palette) {
+        eprintln!("Could not render the poster: {}", e);
+        process::exit(1);
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("poster")
+        .about("Renders a huge PNG tile by tile, resuming an interrupted render")
+        .args(&cli::view_args())
+        .arg(
+            clap::Arg::with_name("size")
+                .long("size")
+                .value_name("WIDTHxHEIGHT")
+                .help("Size of the image, in pixels")
+                .required(true)
+                .validator(|v| cli::validate_size(&v)),
+        )
+        .arg(
+            clap::Arg::with_name("tile")
+                .long("tile")
+                .value_name("N")
+                .help("Side of the tiles, in pixels. The memory used is about 8 x width x N bytes")
+                .default_value("512")
+                .validator(|v| cli::validate_count(&v)),
+        )
+        .arg(
+            clap::Arg::with_name("cpu")
+                .long("cpu")
+                .help("Renders on the CPU even if there is a GPU"),
+        )
+        .arg(cli::device_arg())
+        .arg(
+            clap::Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("FILE")
+                .help("Path of the PNG file to write")
+                .required(true),
+        )
+}
+
+struct Poster {
+    size: [u32; 2],
+    tile: u32,
+    location: Location,
+    output: PathBuf,
+}
+
+impl Poster {
+    fn render(&self, backend: &mut Backend, palette: &Palette) -> Result<(), String> {
+        let [width, height] = self.size;
+        let parts = self.parts_dir()?;
+        let bands = height.div_ceil(self.tile);
+        let columns = width.div_ceil(self.tile);
+        let todo: Vec<u32> = (0..bands)
+            .filter(|&band| !band_path(&parts, band).exists())
+            .collect();
+        if todo.len() < bands as usize {
+            println!("Resuming, {} bands out of {} left", todo.len(), bands);
+        }
+
+        let start = Instant::now();
+        let total = todo.len() as u32 * columns;
+        let mut done = 0;
+        for &band in &todo {
+            let y = band * self.tile;
+            let band_height = self.tile.min(height - y);
+            let mut pixels = vec![0; width as usize * band_height as usize * 4];
+            for column in 0..columns {
+                let x = column * self.tile;
+                let tile_width = self.tile.min(width - x);
+                let view = self.tile_view([x, y], [tile_width, band_height]);
+                let tile = backend.render(&view, palette, tile_width, band_height)?;
+                let row_len = tile_width as usize * 4;
+                for (row, tile_row) in tile.chunks_exact(row_len).enumerate() {
+                    let offset = (row * width as usize + x as usize) * 4;
+                    pixels[offset..offset + row_len].copy_from_slice(tile_row);
+                }
+
+                done += 1;
+                let elapsed = start.elapsed().as_secs_f64();
+                let left = elapsed / f64::from(done) * f64::from(total - done);
+                print!(
+                    "\rTile {}/{} ({:.0}%), {:.0}s left  ",
+                    done,
+                    total,
+                    f64::from(done) / f64::from(total) * 100.,
+                    left
+                );
+                std::io::stdout().flush().ok();
+            }
+            // written aside then renamed, so that a band file is either complete or missing
+            let path = band_path(&parts, band);
+            let tmp = path.with_extension("tmp");
+            png_io::write_png(&tmp, width, band_height, &pixels, &self.location)
+                .map_err(|e| format!("{}: {}", tmp.display(), e))?;
+            fs::rename(&tmp, &path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+        if total > 0 {
+            println!();
+        }
+
+        println!("Writing {}", self.output.display());
+        self.assemble(&parts, bands)?;
+        fs::remove_dir_all(&parts).map_err(|e| format!("{}: {}", parts.display(), e))
+    }
+
+    /// The part of the view covered by the tile at `position` (top left corner) of `size`,
+    /// both in pixels
+    fn tile_view(&self, position: [u32; 2], size: [u32; 2]) -> View {
+        let view = &self.location.view;
+        let [width, height] = [f64::from(self.size[0]), f64::from(self.size[1])];
+        let center = [
+            f64::from(position[0]) + f64::from(size[0]) / 2.,
+            f64::from(position[1]) + f64::from(size[1]) / 2.,
+        ];
+        let mut tile = *view;
+        tile.camera.pan([
+            (2. * center[0] - width) / height,
+            (2. * center[1] - height) / height,
+        ]);
+        tile.camera.zoom *= f64::from(size[1]) / height;
+        // the iterations of the whole image, not those of the zoom of a tile
+        tile.max_iter = view.iterations();
+        tile.auto_iter = false;
+        tile
+    }
+
+    /// Directory of the finished bands, checking that they belong to this poster
+    fn parts_dir(&self) -> Result<PathBuf, String> {
+        let mut name = self.output.file_name().unwrap_or_default().to_owned();
+        name.push(".parts");
+        let parts = self.output.with_file_name(name);
+        let description = format!(
+            "size = {}x{}\ntile = {}\n{}",
+            self.size[0], self.size[1], self.tile, self.location
+        );
+        let description_path = parts.join("poster.txt");
+        let error = |e: std::io::Error| format!("{}: {}", parts.display(), e);
+        if parts.exists() {
+            let previous = fs::read_to_string(&description_path).map_err(error)?;
+            if previous != description {
+                return Err(format!(
+                    "{} holds the tiles of another poster, delete it to start over",
+                    parts.display()
+                ));
+            }
+        } else {
+            fs::create_dir_all(&parts).map_err(error)?;
+            fs::write(&description_path, description).map_err(error)?;
+        }
+        Ok(parts)
+    }
+
+    /// Streams the bands into the output, holding one of them in memory at a time
+    fn assemble(&self, parts: &Path, bands: u32) -> Result<(), String> {
+        let output_error = |e: png::EncodingError| format!("{}: {}", self.output.display(), e);
+        let file = File::create(&self.output).map_err(|e| output_error(e.into()))?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.size[0], self.size[1]);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        png_io::add_location(&mut encoder, &self.location).map_err(output_error)?;
+        let mut writer = encoder.write_header().map_err(output_error)?;
+        let mut stream = writer.stream_writer().map_err(output_error)?;
+        for band in 0..bands {
+            let path = band_path(parts, band);
+            let band_error = |e: png::DecodingError| format!("{}: {}", path.display(), e);
+            let file = File::open(&path).map_err(|e| band_error(e.into()))?;
+            let mut reader = png::Decoder::new(file).read_info().map_err(band_error)?;
+            let mut pixels = vec![0; reader.output_buffer_size()];
+            reader.next_frame(&mut pixels).map_err(band_error)?;
+            stream
+                .write_all(&pixels)
+                .map_err(|e| output_error(e.into()))?;
+        }
+        stream.finish().map_err(output_error)
+    }
+}
+
+fn band_path(parts: &Path, band: u32) -> PathBuf {
+    parts.join(format!("band_{:05}.png", band))
+}
@@ -0,0 +1,388 @@
+
+layout(location = 0) in vec2 pos;
+// pos - view center, precise even when pos is not
+layout(location = 1) in vec2 delta;
+#include "uniforms.glsl"
+// see crate::perturbation::ReferenceOrbit
+layout(std430, binding = 1) readonly buffer Orbit {
+    vec2 points[];
+} orbit;
+
+// see crate::palette::Palette::texels
+#define PALETTE_SIZE 256
+layout(binding = 2) uniform Palette {
+    vec4 colors[PALETTE_SIZE];
+} palette;
+
+layout(location = 0) out vec4 f_color;
+
+// values of crate::fractal::Formula
+#define MANDELBROT 0
+#define BURNING_SHIP 1
+#define TRICORN 2
+#define MULTIBROT 3
+
+// values of crate::coloring::Coloring
+#define LIGHTING 0
+#define SMOOTH 1
+
+// values of crate::view::Precision
+#define SINGLE 0
+#define DOUBLE 1
+#define PERTURBATION 2
+
+float squared_mod(vec2 vec)
+{
+    return (vec.x * vec.x + vec.y * vec.y);
+}
+
+vec2 c_mul(vec2 a, vec2 b)
+{
+    return (vec2(a.x * b.x - a.y * b.y, a.y * b.x + a.x * b.y));
+}
+
+vec2 c_pow(vec2 z, uint n)
+{
+    vec2 res = vec2(1, 0);
+    for (uint k = 0; k < n; k++)
+        res = c_mul(res, z);
+    return (res);
+}
+
+// d_inpc times the derivative of step_z
+vec2 calc_d_inpc(vec2 d_inpc, vec2 z)
+{
+    if (uniforms.formula == BURNING_SHIP)
+    {
+        // abs() flips the components of the derivative along with z's
+        vec2 flip = vec2(z.x < 0 ? -1 : 1, z.y < 0 ? -1 : 1);
+        return (c_mul(d_inpc * flip * 2, abs(z)));
+    }
+    if (uniforms.formula == TRICORN)
+    {
+        d_inpc = c_mul(d_inpc * 2, z);
+        return (vec2(d_inpc.x, -d_inpc.y));
+    }
+    if (uniforms.formula == MULTIBROT)
+        return (c_mul(d_inpc * float(uniforms.power), c_pow(z, uniforms.power - 1)));
+    d_inpc = d_inpc * 2;
+    d_inpc = vec2(
+        d_inpc.x * z.x - d_inpc.y * z.y,
+        d_inpc.y * z.x + d_inpc.x * z.y
+    );
+    return (d_inpc);
+}
+
+vec2 step_z(vec2 z, vec2 c)
+{
+    if (uniforms.formula == BURNING_SHIP)
+        z = abs(z);
+    if (uniforms.formula == TRICORN)
+        z.y = -z.y;
+    if (uniforms.formula == MULTIBROT)
+        return (c_pow(z, uniforms.power) + c);
+    return (vec2(
+        z.x * z.x - z.y * z.y + c.x,
+        z.y * z.x + z.x * z.y + c.y
+    ));
+}
+
+// |c + d| - |c|, without losing d when it is tiny next to c
+float diffabs(float c, float d)
+{
+    if (c >= 0)
+        return (c + d >= 0 ? d : -(2 * c + d));
+    return (c + d > 0 ? 2 * c + d : -d);
+}
+
+// step_z(ref_z + dz, C + dc) - step_z(ref_z, C), computed without ever adding dz to ref_z
+vec2 perturb_z(vec2 ref_z, vec2 dz, vec2 dc)
+{
+    if (uniforms.formula == BURNING_SHIP)
+        return (vec2(
+            (2 * ref_z.x + dz.x) * dz.x - (2 * ref_z.y + dz.y) * dz.y,
+            2 * diffabs(ref_z.x * ref_z.y, ref_z.x * dz.y + dz.x * ref_z.y + dz.x * dz.y)
+        ) + dc);
+    if (uniforms.formula == MULTIBROT)
+    {
+        // (Z + dz)^n - Z^n = dz * sum of (Z + dz)^k * Z^(n - 1 - k)
+        vec2 z = ref_z + dz;
+        vec2 sum = vec2(1, 0);
+        vec2 ref_pow = vec2(1, 0);
+        for (uint k = 1; k < uniforms.power; k++)
+        {
+            ref_pow = c_mul(ref_pow, ref_z);
+            sum = c_mul(sum, z) + ref_pow;
+        }
+        return (c_mul(dz, sum) + dc);
+    }
+    vec2 d = c_mul(2 * ref_z + dz, dz);
+    if (uniforms.formula == TRICORN)
+        d.y = -d.y;
+    return (d + dc);
+}
+
+// Double precision numbers: native doubles when the device has them,
+// otherwise pairs of floats (hi, lo) meaning hi + lo, with |lo| <= ulp(hi) / 2.
+// The float-float operations rely on the exact rounding of each float operation,
+// `precise` keeps the compiler from reordering or fusing them.
+#ifdef NATIVE_DOUBLE
+#define real double
+
+real r_from(float hi, float lo)
+{
+    return (double(hi) + double(lo));
+}
+
+float r_to_float(real a)
+{
+    return (float(a));
+}
+
+real r_add(real a, real b)
+{
+    return (a + b);
+}
+
+real r_mul(real a, real b)
+{
+    return (a * b);
+}
+
+real r_abs(real a)
+{
+    return (abs(a));
+}
+
+real r_neg(real a)
+{
+    return (-a);
+}
+#else
+#define real vec2
+
+// a + b, exactly
+vec2 two_sum(float a, float b)
+{
+    precise float s = a + b;
+    precise float v = s - a;
+    precise float e = (a - (s - v)) + (b - v);
+    return (vec2(s, e));
+}
+
+// a + b, exactly, if |a| >= |b|
+vec2 quick_two_sum(float a, float b)
+{
+    precise float s = a + b;
+    precise float e = b - (s - a);
+    return (vec2(s, e));
+}
+
+// a = hi + lo, both with half of the mantissa bits (Dekker)
+vec2 split(float a)
+{
+    precise float t = 4097. * a;
+    precise float hi = t - (t - a);
+    precise float lo = a - hi;
+    return (vec2(hi, lo));
+}
+
+// a * b, exactly
+vec2 two_prod(float a, float b)
+{
+    precise float p = a * b;
+    precise vec2 sa = split(a);
+    precise vec2 sb = split(b);
+    precise float e = ((sa.x * sb.x - p) + sa.x * sb.y + sa.y * sb.x) + sa.y * sb.y;
+    return (vec2(p, e));
+}
+
+real r_from(float hi, float lo)
+{
+    return (quick_two_sum(hi, lo));
+}
+
+float r_to_float(real a)
+{
+    return (a.x);
+}
+
+real r_add(real a, real b)
+{
+    precise vec2 s = two_sum(a.x, b.x);
+    precise vec2 t = two_sum(a.y, b.y);
+    precise float e = s.y + t.x;
+    s = quick_two_sum(s.x, e);
+    e = s.y + t.y;
+    return (quick_two_sum(s.x, e));
+}
+
+real r_mul(real a, real b)
+{
+    precise vec2 p = two_prod(a.x, b.x);
+    precise float e = p.y + (a.x * b.y + a.y * b.x);
+    return (quick_two_sum(p.x, e));
+}
+
+real r_abs(real a)
+{
+    return (a.x < 0 ? -a : a);
+}
+
+real r_neg(real a)
+{
+    return (-a);
+}
+#endif
+
+struct complex_r {
+    real x;
+    real y;
+};
+
+vec2 complex_r_to_vec2(complex_r z)
+{
+    return (vec2(r_to_float(z.x), r_to_float(z.y)));
+}
+
+complex_r complex_r_mul(complex_r a, complex_r b)
+{
+    return (complex_r(
+        r_add(r_mul(a.x, b.x), r_neg(r_mul(a.y, b.y))),
+        r_add(r_mul(a.y, b.x), r_mul(a.x, b.y))
+    ));
+}
+
+// step_z in double precision
+complex_r step_z_r(complex_r z, complex_r c)
+{
+    if (uniforms.formula == BURNING_SHIP)
+        z = complex_r(r_abs(z.x), r_abs(z.y));
+    if (uniforms.formula == TRICORN)
+        z.y = r_neg(z.y);
+    if (uniforms.formula == MULTIBROT)
+    {
+        complex_r res = z;
+        for (uint k = 1; k < uniforms.power; k++)
+            res = complex_r_mul(res, z);
+        return (complex_r(r_add(res.x, c.x), r_add(res.y, c.y)));
+    }
+    complex_r sq = complex_r_mul(z, z);
+    return (complex_r(r_add(sq.x, c.x), r_add(sq.y, c.y)));
+}
+
+vec2	c_div(vec2 c, vec2 divi)
+{
+	float	re;
+
+	re = c.x;
+	c.x = ((c.x * divi.x) + (c.y * divi.y))
+			/ ((divi.x * divi.x) + (divi.y * divi.y));
+	c.y = ((c.y * divi.x) - (re * divi.y))
+			/ ((divi.x * divi.x) + (divi.y * divi.y));
+	return (c);
+}
+
+// Fractional iteration count at which z escaped, continuous across pixels
+float smooth_iter(uint i, vec2 z)
+{
+    float degree = uniforms.formula == MULTIBROT ? float(uniforms.power) : 2.;
+    return float(i) + 1. - log(log(length(z))) / log(degree);
+}
+
+// The palette repeats every 1, colors are interpolated between its texels
+vec3 palette_color(float t)
+{
+    t = fract(t) * PALETTE_SIZE;
+    uint a = uint(t) % PALETTE_SIZE;
+    uint b = (a + 1u) % PALETTE_SIZE;
+    return mix(palette.colors[a].rgb, palette.colors[b].rgb, fract(t));
+}
+
+void main() {
+    // Mandelbrot: z starts at c, julia: z starts at pos and c is fixed.
+    // For julia c is a constant, so the derivative has no +dc term
+    bool julia = uniforms.julia != 0;
+    float dc = 0.0001;
+    vec2 c = julia ? vec2(uniforms.julia_x, uniforms.julia_y) : pos;
+    vec2 z = pos;
+    vec2 d_inpc = vec2(1, 0);
+    vec2 dd_inpc = vec2(dc, 0);
+    vec2 dd_step = julia ? vec2(0, 0) : vec2(dc, 0);
+    float sqrmod_z;
+
+    // In double precision, the iterations are done on z_r and z follows it
+    complex_r pos_r = complex_r(
+        r_add(r_from(uniforms.pos_x, uniforms.pos_x_lo), r_from(delta.x, 0)),
+        r_add(r_from(uniforms.pos_y, uniforms.pos_y_lo), r_from(delta.y, 0))
+    );
+    complex_r c_r = julia ? complex_r(r_from(uniforms.julia_x, 0), r_from(uniforms.julia_y, 0)) : pos_r;
+    complex_r z_r = pos_r;
+    if (uniforms.precision_mode == DOUBLE)
+        z = complex_r_to_vec2(z_r);
+
+    // With perturbation z = orbit.points[m] + dz, only dz is iterated here
+    bool perturbation = uniforms.precision_mode == PERTURBATION;
+    vec2 offset = delta + vec2(uniforms.ref_offset_x, uniforms.ref_offset_y);
+    vec2 delta_c = julia ? vec2(0, 0) : offset;
+    vec2 dz = offset;
+    uint m = julia ? 0u : 1u;
+    if (perturbation)
+        z = orbit.points[m] + dz;
+
+    uint i;
+    for (i = 0u; i < uniforms.max_iter; i++) {
+        d_inpc = calc_d_inpc(d_inpc, z);
+        dd_inpc = calc_d_inpc(dd_inpc, z) + dd_step;
+        if (perturbation)
+        {
+            // Restart from the beginning of the orbit when it has no next point (the reference
+            // escaped), before reading past its end
+            if (m + 1u >= uniforms.ref_len)
+            {
+                dz = z - orbit.points[0];
+                m = 0u;
+            }
+            dz = perturb_z(orbit.points[m], dz, delta_c);
+            m++;
+            z = orbit.points[m] + dz;
+            // Same when z gets closer to 0 than to the reference (for the Mandelbrot set,
+            // points[0] is 0 so this loses nothing)
+            if (!julia && squared_mod(z) < squared_mod(dz))
+            {
+                dz = z - orbit.points[0];
+                m = 0u;
+            }
+        }
+        else if (uniforms.precision_mode == DOUBLE)
+        {
+            z_r = step_z_r(z_r, c_r);
+            z = complex_r_to_vec2(z_r);
+        }
+        else
+            z = step_z(z, c);
+        if (squared_mod(d_inpc) < 0.0001)
+        {
+            i = uniforms.max_iter;
+			break ;
+		}
+        if (squared_mod(z) > 500)
+			break ;
+    }
+
+    vec3 color = vec3(0.);
+	if (i < uniforms.max_iter && uniforms.coloring == SMOOTH)
+        color = palette_color(smooth_iter(i, z) / uniforms.palette_period + uniforms.palette_offset);
+	else if (i < uniforms.max_iter)
+	{
+        z = c_div(z, dd_inpc);
+        z = c_div(z, abs(z));
+        z.x = (z.x * 0.7071067811865475 + z.y * 0.7071067811865475 + 1.5) / 2.5;
+        if (z.x < 0)
+            z.x = 0;
+        color = vec3(z.x);
+        // color=1.;
+	}
+
+    f_color = vec4(color, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,18 @@
+#version 450
+
+layout(location = 0) in vec2 position;
+#include "uniforms.glsl"
+
+layout(location = 0) out vec2 pos;
+layout(location = 1) out vec2 delta;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    // same as crate::camera::Camera::screen_to_plane
+    float c = cos(uniforms.rotation);
+    float s = sin(uniforms.rotation);
+    delta = mat2(c, s, -s, c) * vec2(position.x * uniforms.aspect, position.y) * uniforms.zoom;
+    pos = delta;
+    pos.x += uniforms.pos_x;
+    pos.y += uniforms.pos_y;
+}
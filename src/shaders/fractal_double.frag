@@ -0,0 +1,5 @@
+#version 450
+
+// for the devices supporting shader_float64
+#define NATIVE_DOUBLE
+#include "fractal.glsl"
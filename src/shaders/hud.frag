@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+layout(location = 1) in vec4 v_color;
+
+// one channel, the coverage of the glyphs
+layout(binding = 0) uniform sampler2D atlas;
+
+layout(location = 0) out vec4 f_color;
+
+void main() {
+    f_color = vec4(v_color.rgb, v_color.a * texture(atlas, v_uv).r);
+}
@@ -0,0 +1,15 @@
+#version 450
+
+// already in normalized device coordinates, see crate::vk_render::hud
+layout(location = 0) in vec2 position;
+layout(location = 1) in vec2 uv;
+layout(location = 2) in vec4 color;
+
+layout(location = 0) out vec2 v_uv;
+layout(location = 1) out vec4 v_color;
+
+void main() {
+    gl_Position = vec4(position, 0.0, 1.0);
+    v_uv = uv;
+    v_color = color;
+}
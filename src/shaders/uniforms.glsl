@@ -0,0 +1,25 @@
+// Must match crate::vk_render::Uniform
+layout(binding = 0) uniform Data {
+    float zoom;
+    // width / height of the screen
+    float aspect;
+    float rotation;
+    float pos_x;
+    float pos_y;
+    // pos + pos_lo is the view center with twice the precision of a float
+    float pos_x_lo;
+    float pos_y_lo;
+    uint julia;
+    float julia_x;
+    float julia_y;
+    uint formula;
+    uint power;
+    uint precision_mode;
+    uint ref_len;
+    float ref_offset_x;
+    float ref_offset_y;
+    uint max_iter;
+    uint coloring;
+    float palette_offset;
+    float palette_period;
+} uniforms;
@@ -0,0 +1,357 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::camera::{self, Camera};
+use crate::location::Location;
+use crate::video::{zoom_between, Easing};
+use crate::view::View;
+
+/// How the views move from one key to the next
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Interpolation {
+    /// one smooth curve through all the keys, without stopping on them
+    CatmullRom,
+    /// stops on each key, zooming between them like the `video` subcommand
+    Eased,
+}
+
+/// A view to be at, at some time
+#[derive(Clone)]
+pub struct Key {
+    /// seconds from the start of the tour
+    pub time: f64,
+    pub view: View,
+}
+
+/// A camera path through keyframes, saved as a list of sections named after their time:
+/// ```text
+/// interpolation = catmull-rom
+///
+/// [0]
+/// center = -1, 0
+/// zoom = 1.5
+///
+/// [12.5]
+/// center = -0.75, 0.1
+/// zoom = 1e-3
+/// rotation = 90
+/// ```
+/// The sections hold the keys of a `Location`, the missing ones keep their value of the
+/// previous key. The center, zoom, rotation, Julia parameter and palette offset follow
+/// the interpolation, the other settings change when their key is reached.
+#[derive(Clone)]
+pub struct Tour {
+    /// at least one, sorted by time
+    pub keys: Vec<Key>,
+    pub interpolation: Interpolation,
+    /// palette of the first key, the others can't change it
+    pub palette: Option<PathBuf>,
+}
+
+impl Tour {
+    pub fn load(path: &Path) -> Result<Tour, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Tour::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn parse(text: &str) -> Result<Tour, String> {
+        let mut interpolation = Interpolation::CatmullRom;
+        let mut keys: Vec<Key> = Vec::new();
+        let mut location = Location {
+            view: View::default(),
+            palette: None,
+        };
+        let mut palette = None;
+        let mut lines = text.lines().enumerate().peekable();
+        while let Some((n, line)) = lines.next() {
+            let line = line.trim();
+            let error = |what: &str| format!("line {}: {} in '{}'", n + 1, what, line);
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let time = match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                Some(time) => time
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| error("invalid time"))?,
+                None if keys.is_empty() => {
+                    interpolation = match line.split_once('=') {
+                        Some((key, value)) if key.trim() == "interpolation" => match value.trim() {
+                            "catmull-rom" => Interpolation::CatmullRom,
+                            "eased" => Interpolation::Eased,
+                            _ => return Err(error("expected catmull-rom or eased")),
+                        },
+                        _ => return Err(error("expected 'interpolation = ...' or '[time]'")),
+                    };
+                    continue;
+                }
+                None => return Err(error("expected '[time]'")),
+            };
+            if !time.is_finite() || keys.last().is_some_and(|key| time <= key.time) {
+                return Err(error("times must increase"));
+            }
+            let mut section = Vec::new();
+            while let Some(&(n, line)) = lines.peek() {
+                if line.trim_start().starts_with('[') {
+                    break;
+                }
+                section.push((n, line));
+                lines.next();
+            }
+            location = location.parse_changes(section.into_iter())?;
+            if keys.is_empty() {
+                palette = location.palette.clone();
+            }
+            keys.push(Key {
+                time,
+                view: location.view,
+            });
+        }
+        if keys.is_empty() {
+            return Err("no keys".to_owned());
+        }
+        Ok(Tour {
+            keys,
+            interpolation,
+            palette,
+        })
+    }
+
+    /// Time of the last key
+    pub fn duration(&self) -> f64 {
+        self.keys.last().unwrap().time
+    }
+
+    /// The view at `time` seconds from the start
+    pub fn view_at(&self, time: f64) -> View {
+        let keys = &self.keys;
+        // index of the key starting the segment we are in
+        let i = match keys.iter().rposition(|key| key.time <= time) {
+            // exactly, where the splines through ln(zoom) would be off by a rounding
+            Some(i) if keys[i].time == time => return keys[i].view,
+            Some(i) if i + 1 < keys.len() => i,
+            Some(_) => return keys[keys.len() - 1].view,
+            None => return keys[0].view,
+        };
+        let (a, b) = (&keys[i], &keys[i + 1]);
+        let u = (time - a.time) / (b.time - a.time);
+        let mut view = a.view;
+        match self.interpolation {
+            Interpolation::Eased => {
+                let between = zoom_between(&a.view, &b.view, Easing::Smooth.apply(u));
+                view.camera = between.camera;
+                view.julia_c = between.julia_c;
+                view.palette_offset = between.palette_offset;
+            }
+            Interpolation::CatmullRom => {
+                // the keys around the segment, repeated at the ends
+                let (prev, next) = (
+                    &keys[i.saturating_sub(1)],
+                    &keys[(i + 2).min(keys.len() - 1)],
+                );
+                let times = [prev.time, a.time, b.time, next.time];
+                let spline = |value: fn(&View) -> f64| {
+                    let values = [prev, a, b, next].map(|key| value(&key.view));
+                    hermite(values, times, time)
+                };
+                let mut center = a.view.camera.center;
+                for (axis, coordinate) in center.iter_mut().enumerate() {
+                    // relative to `a`, as f64 can't hold the coordinates of a deep zoom
+                    let values = [prev, a, b, next]
+                        .map(|key| (key.view.camera.center[axis] - *coordinate).hi);
+                    *coordinate = *coordinate + hermite(values, times, time);
+                }
+                // each angle the nearest turn of its neighbor's, so that they turn the short way
+                let rotation = |key: &Key| f64::from(key.view.camera.rotation);
+                let a_rotation = rotation(a);
+                let b_rotation = camera::nearest_turn(rotation(b), a_rotation);
+                let rotations = [
+                    camera::nearest_turn(rotation(prev), a_rotation),
+                    a_rotation,
+                    b_rotation,
+                    camera::nearest_turn(rotation(next), b_rotation),
+                ];
+                view.camera = Camera {
+                    rotation: hermite(rotations, times, time) as f32,
+                    ..Camera::new(center, spline(|v| v.camera.zoom.ln()).exp())
+                };
+                view.julia_c = [
+                    spline(|v| f64::from(v.julia_c[0])) as f32,
+                    spline(|v| f64::from(v.julia_c[1])) as f32,
+                ];
+                view.palette_offset = spline(|v| f64::from(v.palette_offset)) as f32;
+            }
+        }
+        view
+    }
+}
+
+/// Catmull-Rom spline through `values` at `times`, evaluated at `time` between the middle two:
+/// a cubic Hermite curve whose slope on each key is that of the line joining its neighbors.
+fn hermite(values: [f64; 4], times: [f64; 4], time: f64) -> f64 {
+    let slope = |from: usize, to: usize| (values[to] - values[from]) / (times[to] - times[from]);
+    // at the ends of the tour the neighbor is the key itself, which still gives a slope
+    let m1 = slope(0, 2);
+    let m2 = slope(1, 3);
+    let dt = times[2] - times[1];
+    let u = (time - times[1]) / dt;
+    let (u2, u3) = (u * u, u * u * u);
+    (2. * u3 - 3. * u2 + 1.) * values[1]
+        + (u3 - 2. * u2 + u) * dt * m1
+        + (-2. * u3 + 3. * u2) * values[2]
+        + (u3 - u2) * dt * m2
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::location::radians;
+    use std::f64::consts::TAU;
+
+    fn tour(text: &str) -> Tour {
+        Tour::parse(text).unwrap()
+    }
+
+    /// Difference of two angles, in turns of at most half a turn
+    fn angle_difference(a: f32, b: f32) -> f64 {
+        let d = (f64::from(a) - f64::from(b)).rem_euclid(TAU);
+        d.min(TAU - d)
+    }
+
+    #[test]
+    fn times_must_increase() {
+        for text in [
+            "[0]\n[0]",
+            "[1]\nzoom = 1\n[0.5]",
+            "[nan]",
+            "[inf]",
+            "[soon]",
+        ] {
+            assert!(Tour::parse(text).is_err(), "{:?}", text);
+        }
+        assert!(Tour::parse("").is_err());
+    }
+
+    #[test]
+    fn reads_the_interpolation_header() {
+        assert_eq!(tour("[0]").interpolation, Interpolation::CatmullRom);
+        let eased = tour("# a tour\ninterpolation = eased\n\n[0]\n[1]");
+        assert_eq!(eased.interpolation, Interpolation::Eased);
+        assert!(Tour::parse("interpolation = cubic\n[0]").is_err());
+        // only before the first key
+        assert!(Tour::parse("[0]\ninterpolation = eased").is_err());
+    }
+
+    #[test]
+    fn keys_keep_the_missing_values_of_the_previous_one() {
+        let tour = tour(
+            "[0]\nzoom = 1e-3\niterations = 500\npalette = fire.map\n\
+             [5]\ncenter = 0.3, 0\n\
+             [7.5]\niterations = 800\npalette = ice.map\n",
+        );
+        let keys = &tour.keys;
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[1].view.camera.zoom, 1e-3);
+        assert_eq!(keys[1].view.max_iter, 500);
+        assert_eq!(keys[2].view.camera.center[0], keys[1].view.camera.center[0]);
+        assert_eq!(keys[2].view.max_iter, 800);
+        assert_eq!(tour.palette, Some(PathBuf::from("fire.map")));
+        assert_eq!(tour.duration(), 7.5);
+    }
+
+    #[test]
+    fn hermite_goes_through_the_keys() {
+        let times = [0., 1., 3., 4.];
+        let values = [5., -2., 7., 1.];
+        assert_eq!(hermite(values, times, 1.), -2.);
+        assert_eq!(hermite(values, times, 3.), 7.);
+    }
+
+    #[test]
+    fn hermite_is_linear_on_a_line_and_curves_otherwise() {
+        let times = [0., 1., 2., 3.];
+        let line = [0., 2., 4., 6.];
+        for &t in &[1.25, 1.5, 1.75] {
+            assert!((hermite(line, times, t) - 2. * t).abs() < 1e-12);
+        }
+        // a bump: the curve rises above the straight segment on its way down
+        let bump = [0., 1., 1., 0.];
+        assert!(hermite(bump, times, 1.5) > 1.);
+    }
+
+    #[test]
+    fn view_at_hits_the_keys_and_clamps_past_the_ends() {
+        for interpolation in ["catmull-rom", "eased"] {
+            let tour = tour(&format!(
+                "interpolation = {}\n\
+                 [0]\ncenter = -1, 0\nzoom = 1.5\n\
+                 [2]\ncenter = -0.75, 0.1\nzoom = 1e-3\nrotation = 90\n\
+                 [6]\ncenter = -0.7436, 0.1318\nzoom = 1e-6\n",
+                interpolation
+            ));
+            for (time, key) in [(-1., 0), (0., 0), (2., 1), (6., 2), (60., 2)] {
+                let view = tour.view_at(time);
+                let camera = &tour.keys[key].view.camera;
+                assert_eq!(
+                    view.camera.center, camera.center,
+                    "{} at {}",
+                    interpolation, time
+                );
+                assert_eq!(
+                    view.camera.zoom, camera.zoom,
+                    "{} at {}",
+                    interpolation, time
+                );
+                assert_eq!(
+                    view.camera.rotation, camera.rotation,
+                    "{} at {}",
+                    interpolation, time
+                );
+            }
+            // zooms in all the way, without overshooting the last key
+            let zooms: Vec<f64> = (0..=60)
+                .map(|i| tour.view_at(f64::from(i) * 0.1).camera.zoom)
+                .collect();
+            assert!(
+                zooms.windows(2).all(|w| w[1] <= w[0]),
+                "{}: {:?}",
+                interpolation,
+                zooms
+            );
+        }
+    }
+
+    #[test]
+    fn catmull_rom_keeps_moving_through_keys_where_eased_stops() {
+        let keys = "[0]\ncenter = 0, 0\n[1]\ncenter = 1, 0\n[2]\ncenter = 2, 0\n";
+        let x = |tour: &Tour, time: f64| tour.view_at(time).camera.center[0].hi;
+        let spline = tour(keys);
+        let eased = tour(&format!("interpolation = eased\n{}", keys));
+        // evenly spaced keys on a line: the spline moves at a constant speed
+        assert!((x(&spline, 0.5) - 0.5).abs() < 1e-12);
+        assert!((x(&spline, 1.1) - 1.1).abs() < 1e-12);
+        // the eased tour slows down to a stop on each key
+        assert!(x(&eased, 1.1) - 1. < 0.05);
+        assert!(x(&eased, 0.9) > 0.95);
+    }
+
+    #[test]
+    fn rotation_turns_the_short_way() {
+        for interpolation in ["catmull-rom", "eased"] {
+            let tour = tour(&format!(
+                "interpolation = {}\n[0]\nrotation = 350\n[2]\nrotation = 10\n[4]\nrotation = 350\n",
+                interpolation
+            ));
+            for (time, degrees) in [(0.5, 355.), (1., 0.), (1.5, 5.), (3., 0.)] {
+                let rotation = tour.view_at(time).camera.rotation;
+                assert!(
+                    angle_difference(rotation, radians(degrees)) < 0.05,
+                    "{} at {}: {} degrees",
+                    interpolation,
+                    time,
+                    f64::from(rotation).to_degrees()
+                );
+            }
+        }
+    }
+}
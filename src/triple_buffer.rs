@@ -0,0 +1,117 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering::*};
+use std::sync::Arc;
+
+/// Set in `Shared::back` when it holds a value the reader has not seen yet
+const DIRTY: usize = 0b100;
+const INDEX: usize = 0b11;
+
+/// Three slots: one being written, one being read, and the latest complete value
+/// waiting in between. The writer and the reader only ever swap their slot with the
+/// middle one, so neither of them waits for the other.
+struct Shared<T> {
+    slots: [UnsafeCell<T>; 3],
+    /// index of the middle slot, | DIRTY
+    back: AtomicUsize,
+}
+
+// Each slot is only accessed by whoever owns its index, and indexes change
+// hands through `back`, whose AcqRel swaps order the slot accesses.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+/// Publishing side of a `triple_buffer`
+pub struct Writer<T> {
+    shared: Arc<Shared<T>>,
+    index: usize,
+}
+
+/// Receiving side of a `triple_buffer`
+pub struct Reader<T> {
+    shared: Arc<Shared<T>>,
+    index: usize,
+}
+
+/// Lock-free single producer, single consumer handoff of the latest value:
+/// the reader gets the last value written, skipping those it was too slow to see.
+pub fn triple_buffer<T: Copy + Send>(initial: T) -> (Writer<T>, Reader<T>) {
+    let shared = Arc::new(Shared {
+        slots: [
+            UnsafeCell::new(initial),
+            UnsafeCell::new(initial),
+            UnsafeCell::new(initial),
+        ],
+        back: AtomicUsize::new(1),
+    });
+    let writer = Writer {
+        shared: shared.clone(),
+        index: 0,
+    };
+    let reader = Reader { shared, index: 2 };
+    (writer, reader)
+}
+
+impl<T: Copy> Writer<T> {
+    pub fn write(&mut self, value: T) {
+        // safe: nobody else has our index
+        unsafe { *self.shared.slots[self.index].get() = value };
+        self.index = self.shared.back.swap(self.index | DIRTY, AcqRel) & INDEX;
+    }
+}
+
+impl<T: Copy> Reader<T> {
+    /// The last value written
+    pub fn read(&mut self) -> T {
+        // only the reader clears DIRTY, so if it is set the swap gets a new value
+        if self.shared.back.load(Relaxed) & DIRTY != 0 {
+            self.index = self.shared.back.swap(self.index, AcqRel) & INDEX;
+        }
+        // safe: nobody else has our index
+        unsafe { *self.shared.slots[self.index].get() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// Big enough that a torn write would show up as mismatched fields
+    #[derive(Clone, Copy)]
+    struct State([u64; 32]);
+
+    #[test]
+    fn concurrent_reads_and_writes() {
+        const WRITES: u64 = 1_000_000;
+        let (mut writer, mut reader) = triple_buffer(State([0; 32]));
+
+        let writing = thread::spawn(move || {
+            for n in 1..=WRITES {
+                writer.write(State([n; 32]));
+            }
+        });
+        let reading = thread::spawn(move || {
+            let mut last = 0;
+            while last != WRITES {
+                let state = reader.read();
+                let n = state.0[0];
+                assert!(state.0.iter().all(|&v| v == n), "torn read");
+                assert!(n >= last, "went back from {} to {}", last, n);
+                last = n;
+            }
+        });
+        writing.join().unwrap();
+        reading.join().unwrap();
+    }
+
+    #[test]
+    fn reads_latest_value() {
+        let (mut writer, mut reader) = triple_buffer(0);
+        assert_eq!(reader.read(), 0);
+        writer.write(1);
+        writer.write(2);
+        assert_eq!(reader.read(), 2);
+        assert_eq!(reader.read(), 2);
+        writer.write(3);
+        assert_eq!(reader.read(), 3);
+    }
+}
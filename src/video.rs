@@ -0,0 +1,387 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::Instant;
+
+use crate::backend::Backend;
+use crate::bookmarks::Bookmarks;
+use crate::camera;
+use crate::cli;
+use crate::location::Location;
+use crate::palette::Palette;
+use crate::png_io::write_png;
+use crate::tour::Tour;
+use crate::view::View;
+
+/// Renders a zoom from one location to another, or a `Tour`, as numbered PNGs or a Y4M video
+pub fn render(args: &clap::ArgMatches) {
+    let exit = |what: &str, e: String| -> ! {
+        eprintln!("Could not {}: {}", what, e);
+        process::exit(1);
+    };
+    let size = cli::parse_pair(args.value_of("size").unwrap(), 'x');
+    let fps: u32 = cli::parse(args.value_of("fps").unwrap());
+
+    let (mut video, frame_view): (Video, Box<dyn Fn(u32) -> View>) =
+        if let Some(path) = args.value_of("tour") {
+            let tour = Tour::load(Path::new(path)).unwrap_or_else(|e| exit("read the tour", e));
+            let video = Video {
+                size,
+                fps,
+                frames: (tour.duration() * f64::from(fps)).round() as u32 + 1,
+                palette: tour.palette.clone(),
+            };
+            (
+                video,
+                Box::new(move |frame| tour.view_at(f64::from(frame) / f64::from(fps))),
+            )
+        } else {
+            let end = cli::location(args).unwrap_or_else(|e| exit("read the location", e));
+            let start = match (args.value_of("from"), args.value_of("from-bookmark")) {
+                (Some(path), _) => Location::load(Path::new(path)).map(|l| l.view),
+                (None, Some(name)) => {
+                    Bookmarks::load().and_then(|bookmarks| match bookmarks.get(name) {
+                        Some(location) => Ok(location.view),
+                        None => Err(format!("no bookmark named '{}'", name)),
+                    })
+                }
+                // the whole set, zooming into the end
+                (None, None) => Ok(View {
+                    camera: View::default().camera,
+                    ..end.view
+                }),
+            }
+            .unwrap_or_else(|e| exit("read the start location", e));
+            let easing = match args.value_of("easing").unwrap() {
+                "linear" => Easing::Linear,
+                _ => Easing::Smooth,
+            };
+            let frames: u32 = cli::parse(args.value_of("frames").unwrap());
+            let video = Video {
+                size,
+                fps,
+                frames,
+                palette: end.palette.clone(),
+            };
+            let frame_view = move |frame: u32| {
+                let t = if frames > 1 {
+                    f64::from(frame) / f64::from(frames - 1)
+                } else {
+                    1.
+                };
+                zoom_between(&start, &end.view, easing.apply(t))
+            };
+            (video, Box::new(frame_view))
+        };
+
+    let palette =
+        cli::load_palette(args, &mut video.palette).unwrap_or_else(|e| exit("load the palette", e));
+    let output = Path::new(args.value_of("output").unwrap());
+    let mut backend = Backend::new(args.is_present("cpu"), &cli::device(args), &palette);
+    if let Err(e) = video.render(output, &mut backend, &palette, frame_view) {
+        exit("render the video", e);
+    }
+}
+
+pub fn subcommand<'a, 'b>() -> clap::App<'a, 'b> {
+    clap::SubCommand::with_name("video")
+        .about("Renders a zoom toward a location or a tour, as numbered PNGs or a Y4M video")
+        .args(&cli::view_args())
+        .arg(
+            clap::Arg::with_name("from")
+                .long("from")
+                .value_name("FILE")
+                .help("Location file or fractol PNG to start from [default: the whole set]")
+                .conflicts_with("from-bookmark"),
+        )
+        .arg(
+            clap::Arg::with_name("from-bookmark")
+                .long("from-bookmark")
+                .value_name("NAME")
+                .help("Bookmark to start from"),
+        )
+        .arg(
+            clap::Arg::with_name("tour")
+                .long("tour")
+                .value_name("FILE")
+                .help("Renders a tour instead, at --fps frames per second")
+                .conflicts_with_all(&["from", "from-bookmark", "frames", "easing"]),
+        )
+        .arg(
+            clap::Arg::with_name("frames")
+                .long("frames")
+                .value_name("N")
+                .help("Number of frames")
+                .default_value("300")
+                .validator(|v| cli::validate_count(&v)),
+        )
+        .arg(
+            clap::Arg::with_name("fps")
+                .long("fps")
+                .value_name("N")
+                .help("Frame rate of the tours, and written in the Y4M header")
+                .default_value("30")
+                .validator(|v| cli::validate_count(&v)),
+        )
+        .arg(
+            clap::Arg::with_name("easing")
+                .long("easing")
+                .value_name("EASING")
+                .help("How the movement starts and stops")
+                .possible_values(&["linear", "smooth"])
+                .default_value("smooth"),
+        )
+        .arg(
+            clap::Arg::with_name("size")
+                .long("size")
+                .value_name("WIDTHxHEIGHT")
+                .help("Size of the frames, in pixels")
+                .default_value("1280x720")
+                .validator(|v| cli::validate_size(&v)),
+        )
+        .arg(
+            clap::Arg::with_name("cpu")
+                .long("cpu")
+                .help("Renders on the CPU even if there is a GPU"),
+        )
+        .arg(cli::device_arg())
+        .arg(
+            clap::Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .value_name("PATH")
+                .help("A .y4m file, or a directory for the frame_NNNNN.png files")
+                .required(true),
+        )
+}
+
+#[derive(Clone, Copy)]
+pub enum Easing {
+    Linear,
+    /// starts and stops gently (smoothstep)
+    Smooth,
+}
+
+impl Easing {
+    /// Progress at time `t`, both between 0 and 1
+    pub fn apply(self, t: f64) -> f64 {
+        match self {
+            Easing::Linear => t,
+            Easing::Smooth => t * t * (3. - 2. * t),
+        }
+    }
+}
+
+/// The view at `s` (0 to 1) of the way from `start` to `end`.
+/// The zoom changes by the same factor on every step, and the center follows it so that
+/// the end center drifts toward the middle of the screen as we zoom in on it,
+/// instead of flying by at a speed that grows with the magnification.
+/// What can't be interpolated, like the formula, comes from `end`.
+pub fn zoom_between(start: &View, end: &View, s: f64) -> View {
+    // both exact at the ends
+    let lerp = |a: f64, b: f64| a * (1. - s) + b * s;
+    let (start_zoom, end_zoom) = (start.camera.zoom, end.camera.zoom);
+    let zoom = start_zoom.powf(1. - s) * end_zoom.powf(s);
+    // 1 at the start, 0 at the end, proportional to the zoom in between
+    let f = if start_zoom == end_zoom {
+        1. - s
+    } else {
+        (zoom - end_zoom) / (start_zoom - end_zoom)
+    };
+    let mut view = *end;
+    for axis in 0..2 {
+        view.camera.center[axis] =
+            end.camera.center[axis] + (start.camera.center[axis] - end.camera.center[axis]) * f;
+    }
+    view.camera.zoom = zoom;
+    let start_rotation = f64::from(start.camera.rotation);
+    view.camera.rotation = lerp(
+        start_rotation,
+        camera::nearest_turn(f64::from(end.camera.rotation), start_rotation),
+    ) as f32;
+    for axis in 0..2 {
+        view.julia_c[axis] =
+            lerp(f64::from(start.julia_c[axis]), f64::from(end.julia_c[axis])) as f32;
+    }
+    view.max_iter = lerp(f64::from(start.max_iter), f64::from(end.max_iter)).round() as u32;
+    view.palette_offset = lerp(
+        f64::from(start.palette_offset),
+        f64::from(end.palette_offset),
+    ) as f32;
+    view.palette_period = lerp(
+        f64::from(start.palette_period),
+        f64::from(end.palette_period),
+    ) as f32;
+    view
+}
+
+/// A sequence of frames and where it goes
+pub struct Video {
+    pub size: [u32; 2],
+    pub fps: u32,
+    pub frames: u32,
+    /// saved in the PNG frames along with their view
+    pub palette: Option<PathBuf>,
+}
+
+impl Video {
+    /// Renders the views given by `frame_view` for each frame number to `output`:
+    /// a Y4M video if it ends with `.y4m`, numbered PNGs in that directory otherwise
+    pub fn render(
+        &self,
+        output: &Path,
+        backend: &mut Backend,
+        palette: &Palette,
+        frame_view: impl Fn(u32) -> View,
+    ) -> Result<(), String> {
+        let [width, height] = self.size;
+        let error = |e: std::io::Error| format!("{}: {}", output.display(), e);
+        let is_y4m = output
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("y4m"));
+        let mut y4m = if is_y4m {
+            let mut file = BufWriter::new(File::create(output).map_err(error)?);
+            // 4:4:4 so that no color is lost, ffmpeg subsamples it if asked to
+            writeln!(
+                file,
+                "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444",
+                width, height, self.fps
+            )
+            .map_err(error)?;
+            Some(file)
+        } else {
+            fs::create_dir_all(output).map_err(error)?;
+            None
+        };
+
+        let start = Instant::now();
+        for frame in 0..self.frames {
+            let view = frame_view(frame);
+            let pixels = backend.render(&view, palette, width, height)?;
+            match &mut y4m {
+                Some(file) => {
+                    file.write_all(b"FRAME\n").map_err(error)?;
+                    file.write_all(&to_yuv444(&pixels)).map_err(error)?;
+                }
+                None => {
+                    let path = output.join(format!("frame_{:05}.png", frame));
+                    let location = Location {
+                        view,
+                        palette: self.palette.clone(),
+                    };
+                    write_png(&path, width, height, &pixels, &location)
+                        .map_err(|e| format!("{}: {}", path.display(), e))?;
+                }
+            }
+
+            let done = frame + 1;
+            let left =
+                start.elapsed().as_secs_f64() / f64::from(done) * f64::from(self.frames - done);
+            print!("\rFrame {}/{}, {:.0}s left  ", done, self.frames, left);
+            std::io::stdout().flush().ok();
+        }
+        println!();
+        if let Some(mut file) = y4m {
+            file.flush().map_err(error)?;
+        }
+        Ok(())
+    }
+}
+
+/// The Y, U and V planes of RGBA8 pixels, in BT.601 limited range like Y4M expects by default
+fn to_yuv444(rgba: &[u8]) -> Vec<u8> {
+    let pixels = rgba.len() / 4;
+    let mut yuv = vec![0; pixels * 3];
+    for (i, pixel) in rgba.chunks_exact(4).enumerate() {
+        let [r, g, b] = [
+            f32::from(pixel[0]),
+            f32::from(pixel[1]),
+            f32::from(pixel[2]),
+        ];
+        yuv[i] = (16. + 0.257 * r + 0.504 * g + 0.098 * b).round() as u8;
+        yuv[pixels + i] = (128. - 0.148 * r - 0.291 * g + 0.439 * b).round() as u8;
+        yuv[2 * pixels + i] = (128. + 0.439 * r - 0.368 * g - 0.071 * b).round() as u8;
+    }
+    yuv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::Camera;
+    use crate::double_double::DoubleDouble;
+
+    fn views() -> (View, View) {
+        let start = View::default();
+        let deep = DoubleDouble::from(-0.743_643_887_037_151) + DoubleDouble::from(1.3e-20);
+        let end = View {
+            camera: Camera {
+                rotation: 1.,
+                ..Camera::new([deep, DoubleDouble::from(0.131_825_904_205_33)], 2e-14)
+            },
+            julia_c: [0.3, -0.5],
+            max_iter: 3000,
+            palette_offset: 0.25,
+            ..View::default()
+        };
+        (start, end)
+    }
+
+    #[test]
+    fn zoom_between_hits_both_ends() {
+        let (start, end) = views();
+        for (s, view) in [(0., &start), (1., &end)] {
+            let between = zoom_between(&start, &end, s);
+            assert_eq!(between.camera.center, view.camera.center, "at {}", s);
+            assert_eq!(between.camera.zoom, view.camera.zoom, "at {}", s);
+            assert_eq!(between.camera.rotation, view.camera.rotation, "at {}", s);
+            assert_eq!(between.julia_c, view.julia_c, "at {}", s);
+            assert_eq!(between.max_iter, view.max_iter, "at {}", s);
+            assert_eq!(between.palette_offset, view.palette_offset, "at {}", s);
+        }
+    }
+
+    #[test]
+    fn zoom_between_zooms_by_the_same_factor_on_every_step() {
+        let (start, end) = views();
+        let middle = zoom_between(&start, &end, 0.5);
+        let geometric_mean = (start.camera.zoom * end.camera.zoom).sqrt();
+        assert!((middle.camera.zoom / geometric_mean - 1.).abs() < 1e-12);
+        let zooms: Vec<f64> = (0..=10)
+            .map(|i| zoom_between(&start, &end, f64::from(i) / 10.).camera.zoom)
+            .collect();
+        let ratio = zooms[1] / zooms[0];
+        for pair in zooms.windows(2) {
+            assert!((pair[1] / pair[0] / ratio - 1.).abs() < 1e-9);
+        }
+        // halfway in, the end center is as many screens away as the zoom shrank
+        let distance = |view: &View| (view.camera.center[0] - end.camera.center[0]).abs().hi;
+        let shrunk = (middle.camera.zoom - end.camera.zoom) / (start.camera.zoom - end.camera.zoom);
+        assert!((distance(&middle) / distance(&start) / shrunk - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn easing_starts_and_stops_at_the_ends() {
+        for easing in [Easing::Linear, Easing::Smooth] {
+            assert_eq!(easing.apply(0.), 0.);
+            assert_eq!(easing.apply(1.), 1.);
+            assert_eq!(easing.apply(0.5), 0.5);
+        }
+        assert_eq!(Easing::Linear.apply(0.1), 0.1);
+        // slow at the ends, then catching up
+        let smooth = |t| Easing::Smooth.apply(t);
+        assert!(smooth(0.1) < 0.1 && smooth(0.9) > 0.9);
+        assert!(smooth(0.01) - smooth(0.) < (smooth(0.51) - smooth(0.5)) / 10.);
+    }
+
+    #[test]
+    fn converts_to_planar_limited_range_yuv() {
+        let rgba = [
+            0, 0, 0, 255, // black
+            255, 255, 255, 255, // white
+            255, 0, 0, 0, // red, ignoring alpha
+        ];
+        assert_eq!(to_yuv444(&rgba), [16, 235, 82, 128, 128, 90, 128, 128, 240]);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::camera::Camera;
+use crate::coloring::Coloring;
+use crate::double_double::DoubleDouble;
+use crate::fractal::Formula;
+use crate::perturbation::ReferenceOrbit;
+use crate::vk_render::Uniform;
+
+/// Below this zoom, f32 coordinates are too coarse and the image turns into blocks
+const SINGLE_MIN_ZOOM: f64 = 1e-5;
+/// Same for double precision (f64 or float-float), past this we perturb a reference orbit
+const DOUBLE_MIN_ZOOM: f64 = 1e-11;
+
+pub const MIN_ITERATIONS: u32 = 10;
+/// More and a frame takes long enough for the driver to reset the device
+pub const MAX_ITERATIONS: u32 = 100_000;
+
+/// How the shaders compute the coordinates of the points, see `fractal.glsl`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Precision {
+    Single = 0,
+    /// f64 if the device supports it, float-float otherwise
+    Double = 1,
+    /// f32 offsets to a `ReferenceOrbit`
+    Perturbation = 2,
+}
+
+/// What to draw: written by the physics thread, read by the renderers
+#[derive(Clone, Copy)]
+pub struct View {
+    pub camera: Camera,
+    /// draw the Julia set of `julia_c` instead of the Mandelbrot set
+    pub julia: bool,
+    pub julia_c: [f32; 2],
+    pub formula: Formula,
+    /// exponent of `Formula::Multibrot`
+    pub power: u32,
+    /// iterations before a point is considered inside the set
+    pub max_iter: u32,
+    /// scale `max_iter` with the zoom depth, see `iterations()`
+    pub auto_iter: bool,
+    pub coloring: Coloring,
+    /// shifts the palette, 1 being a whole turn
+    pub palette_offset: f32,
+    /// iterations over which the palette spreads before repeating
+    pub palette_period: f32,
+    /// slowly move `palette_offset`, so the colors flow out of the set
+    pub palette_cycling: bool,
+}
+
+impl Default for View {
+    /// The whole Mandelbrot set
+    fn default() -> View {
+        View {
+            camera: Camera::new([DoubleDouble::from(-1.), DoubleDouble::from(0.)], 0.5),
+            julia: false,
+            julia_c: [0., 0.],
+            formula: Formula::Mandelbrot,
+            power: 3,
+            max_iter: 100,
+            auto_iter: false,
+            coloring: Coloring::Lighting,
+            palette_offset: 0.,
+            palette_period: 50.,
+            palette_cycling: false,
+        }
+    }
+}
+
+impl View {
+    /// The cheapest precision that can draw this view
+    pub fn precision(&self) -> Precision {
+        if self.camera.zoom >= SINGLE_MIN_ZOOM {
+            Precision::Single
+        } else if self.camera.zoom >= DOUBLE_MIN_ZOOM {
+            Precision::Double
+        } else {
+            Precision::Perturbation
+        }
+    }
+
+    /// Iterations the shaders actually do. In auto mode `max_iter` is the count
+    /// at zoom 1/e, growing with -ln(zoom) as deeper zooms need more iterations.
+    pub fn iterations(&self) -> u32 {
+        if !self.auto_iter {
+            return self.max_iter;
+        }
+        let depth = (-self.camera.zoom.ln()).max(1.);
+        (f64::from(self.max_iter) * depth).min(f64::from(MAX_ITERATIONS)) as u32
+    }
+
+    /// Shader parameters, `reference` being the orbit to perturb
+    /// (must be provided when `precision()` is `Perturbation`)
+    /// and `aspect` the width / height ratio of the image
+    pub fn uniform(&self, reference: Option<&ReferenceOrbit>, aspect: f32) -> Uniform {
+        let ref_offset = reference.map_or([0., 0.], |r| r.offset(self));
+        let [position_x, pos_x_lo] = self.camera.center[0].to_f32_pair();
+        let [position_y, pos_y_lo] = self.camera.center[1].to_f32_pair();
+        Uniform {
+            zoom: self.camera.zoom as f32,
+            aspect,
+            rotation: self.camera.rotation,
+            position_x,
+            position_y,
+            pos_x_lo,
+            pos_y_lo,
+            julia: self.julia as u32,
+            julia_x: self.julia_c[0],
+            julia_y: self.julia_c[1],
+            formula: self.formula as u32,
+            power: self.power,
+            precision_mode: self.precision() as u32,
+            ref_len: reference.map_or(0, |r| r.points.len() as u32),
+            ref_offset_x: ref_offset[0],
+            ref_offset_y: ref_offset[1],
+            max_iter: self.iterations(),
+            coloring: self.coloring as u32,
+            palette_offset: self.palette_offset,
+            palette_period: self.palette_period,
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use vulkano::instance::{
+    Instance, InstanceExtensions, PhysicalDevice, PhysicalDeviceType, QueueFamily,
+};
+
+/// Device types from the most to the least wanted. The CPU implementations, like lavapipe,
+/// are only used when there is no GPU
+const PREFERENCE: [PhysicalDeviceType; 5] = [
+    PhysicalDeviceType::DiscreteGpu,
+    PhysicalDeviceType::IntegratedGpu,
+    PhysicalDeviceType::VirtualGpu,
+    PhysicalDeviceType::Cpu,
+    PhysicalDeviceType::Other,
+];
+
+/// Which Vulkan device to draw with
+#[derive(Clone, Debug, PartialEq)]
+pub enum DeviceChoice {
+    /// the first that works, in the order of `PREFERENCE`
+    Auto,
+    /// as numbered by `--list-devices`
+    Index(usize),
+    /// the first of this type
+    Type(PhysicalDeviceType),
+    /// the first whose name contains this, ignoring the case
+    Name(String),
+}
+
+fn type_name(ty: PhysicalDeviceType) -> &'static str {
+    match ty {
+        PhysicalDeviceType::DiscreteGpu => "discrete",
+        PhysicalDeviceType::IntegratedGpu => "integrated",
+        PhysicalDeviceType::VirtualGpu => "virtual",
+        PhysicalDeviceType::Cpu => "cpu",
+        PhysicalDeviceType::Other => "other",
+    }
+}
+
+impl FromStr for DeviceChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DeviceChoice, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("expected a device name, index or type".to_owned());
+        }
+        if s == "auto" {
+            return Ok(DeviceChoice::Auto);
+        }
+        if let Ok(index) = s.parse() {
+            return Ok(DeviceChoice::Index(index));
+        }
+        Ok(PREFERENCE
+            .iter()
+            .find(|&&ty| type_name(ty) == s)
+            .map_or_else(
+                || DeviceChoice::Name(s.to_owned()),
+                |&ty| DeviceChoice::Type(ty),
+            ))
+    }
+}
+
+impl fmt::Display for DeviceChoice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeviceChoice::Auto => f.write_str("auto"),
+            DeviceChoice::Index(index) => write!(f, "{}", index),
+            DeviceChoice::Type(ty) => f.write_str(type_name(*ty)),
+            DeviceChoice::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+impl DeviceChoice {
+    fn matches(&self, physical: &PhysicalDevice) -> bool {
+        match self {
+            DeviceChoice::Auto => true,
+            DeviceChoice::Index(index) => physical.index() == *index,
+            DeviceChoice::Type(ty) => physical.ty() == *ty,
+            DeviceChoice::Name(name) => physical
+                .name()
+                .to_lowercase()
+                .contains(&name.to_lowercase()),
+        }
+    }
+}
+
+/// One line describing `physical`, starting with its number for `--device`
+fn describe(physical: &PhysicalDevice) -> String {
+    format!(
+        "{}: {} ({}, Vulkan {})",
+        physical.index(),
+        physical.name(),
+        type_name(physical.ty()),
+        physical.api_version()
+    )
+}
+
+/// Prints the Vulkan devices, for `--list-devices`
+pub fn list() -> Result<(), String> {
+    let instance =
+        Instance::new(None, &InstanceExtensions::none(), None).map_err(|e| e.to_string())?;
+    let devices: Vec<String> = PhysicalDevice::enumerate(&instance)
+        .map(|physical| describe(&physical))
+        .collect();
+    if devices.is_empty() {
+        println!("No Vulkan device");
+    }
+    for device in devices {
+        println!("{}", device);
+    }
+    Ok(())
+}
+
+/// The device picked by `choice` and its first queue family that is `usable`,
+/// `need` saying what usable means for the error listing the devices if there is none
+pub fn select<'a>(
+    instance: &'a Arc<Instance>,
+    choice: &DeviceChoice,
+    need: &str,
+    usable: impl Fn(QueueFamily) -> bool,
+) -> Result<(PhysicalDevice<'a>, QueueFamily<'a>), String> {
+    let mut candidates: Vec<PhysicalDevice> = PhysicalDevice::enumerate(instance)
+        .filter(|physical| choice.matches(physical))
+        .collect();
+    // stable, the devices of a type stay in the order of the driver
+    candidates.sort_by_key(|physical| PREFERENCE.iter().position(|&ty| ty == physical.ty()));
+    for physical in &candidates {
+        if let Some(queue_family) = physical.queue_families().find(|&q| usable(q)) {
+            return Ok((*physical, queue_family));
+        }
+    }
+
+    let devices: Vec<String> = PhysicalDevice::enumerate(instance)
+        .map(|physical| {
+            let reason = if choice.matches(&physical) {
+                format!("no queue family {}", need)
+            } else {
+                format!("not '{}'", choice)
+            };
+            format!("  {}: {}", describe(&physical), reason)
+        })
+        .collect();
+    if devices.is_empty() {
+        Err("no Vulkan device, is a Vulkan driver installed? \
+             Without a GPU, lavapipe (part of Mesa) draws on the CPU"
+            .to_owned())
+    } else {
+        Err(format!(
+            "no usable Vulkan device for --device {}:\n{}",
+            choice,
+            devices.join("\n")
+        ))
+    }
+}
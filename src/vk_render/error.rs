@@ -0,0 +1,75 @@
+use std::fmt;
+
+/// Why the GPU can't draw, each with the details given by Vulkan
+#[derive(Debug)]
+pub enum RenderError {
+    /// Vulkan is not installed, or too old
+    Instance(String),
+    /// the window or its surface could not be created
+    Window(String),
+    /// no device fits, or it could not be opened
+    Device(String),
+    /// the swapchain could not be created, recreated, or give an image
+    Swapchain(String),
+    /// the shaders could not be loaded, or the pipelines built with them
+    Shader(String),
+    /// the resources of a frame could not be allocated, or the GPU refused its commands
+    Submit(String),
+}
+
+impl RenderError {
+    /// What `main` exits with. 1 is left to the errors that are not about the GPU
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RenderError::Instance(_) => 2,
+            RenderError::Window(_) => 3,
+            RenderError::Device(_) => 4,
+            RenderError::Swapchain(_) => 5,
+            RenderError::Shader(_) => 6,
+            RenderError::Submit(_) => 7,
+        }
+    }
+
+    /// What the user can do about it
+    pub fn hint(&self) -> &'static str {
+        match self {
+            RenderError::Instance(_) => {
+                "Install the Vulkan loader (libvulkan1 or vulkan-loader) and the driver of the GPU. \
+                 Without a GPU, lavapipe (mesa-vulkan-drivers) draws on the CPU, or the render, \
+                 poster and video subcommands can use --cpu."
+            }
+            RenderError::Window(_) => {
+                "Check that a display server is running (DISPLAY or WAYLAND_DISPLAY is set), \
+                 or use the render, poster and video subcommands, which need no window."
+            }
+            RenderError::Device(_) => {
+                "See --list-devices and choose another one with --device, \
+                 or update the driver of the GPU."
+            }
+            RenderError::Swapchain(_) => {
+                "Try another --present-mode or window size, or update the driver of the GPU."
+            }
+            RenderError::Shader(_) => {
+                "The GPU may lack a feature the shaders need: try another --device, \
+                 or update the driver of the GPU."
+            }
+            RenderError::Submit(_) => {
+                "The GPU may be out of memory, or its driver may have crashed (device lost): \
+                 try fewer iterations, a smaller window, or another --device."
+            }
+        }
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RenderError::Instance(e) => write!(f, "Could not load Vulkan: {}", e),
+            RenderError::Window(e) => write!(f, "Could not create the window: {}", e),
+            RenderError::Device(e) => write!(f, "Could not open a GPU: {}", e),
+            RenderError::Swapchain(e) => write!(f, "Could not present to the window: {}", e),
+            RenderError::Shader(e) => write!(f, "Could not load the shaders: {}", e),
+            RenderError::Submit(e) => write!(f, "Could not draw a frame: {}", e),
+        }
+    }
+}
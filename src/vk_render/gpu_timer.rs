@@ -0,0 +1,210 @@
+use crate::vk_render::RenderError;
+use std::collections::VecDeque;
+use std::os::raw::{c_char, c_void};
+use std::sync::Arc;
+use vulkano::buffer::BufferAccess;
+use vulkano::command_buffer::pool::standard::{
+    StandardCommandPoolAlloc, StandardCommandPoolBuilder,
+};
+use vulkano::command_buffer::sys::{Flags, Kind, UnsafeCommandBuffer, UnsafeCommandBufferBuilder};
+use vulkano::command_buffer::{CommandBuffer, CommandBufferExecError};
+use vulkano::device::{Device, DeviceOwned, Queue};
+use vulkano::image::{ImageAccess, ImageLayout};
+use vulkano::instance::{loader, Instance};
+use vulkano::query::{QueryType, UnsafeQueryPool};
+use vulkano::sync::{AccessCheckError, AccessFlagBits, GpuFuture, PipelineStages};
+use vulkano::{OomError, VulkanObject};
+
+/// Frames that can be measured at once. More than can be in flight, so that the queries of a
+/// frame are read before they are reused
+const SLOTS: u32 = 8;
+/// `VK_SUCCESS`, `VK_QUERY_RESULT_64_BIT`
+const SUCCESS: u32 = 0;
+const RESULT_64_BIT: u32 = 1;
+
+/// `vkGetQueryPoolResults`, which vulkano does not wrap
+type GetQueryPoolResults = unsafe extern "system" fn(
+    device: usize,
+    query_pool: u64,
+    first_query: u32,
+    query_count: u32,
+    data_size: usize,
+    data: *mut c_void,
+    stride: u64,
+    flags: u32,
+) -> u32;
+
+/// Measures how long the GPU takes to run the commands of each frame, by writing timestamps
+/// before and after them. The results come a few frames later, once the GPU is done.
+pub struct GpuTimer {
+    queue: Arc<Queue>,
+    /// two timestamps per slot, the start and the end
+    pool: Arc<UnsafeQueryPool>,
+    get_results: GetQueryPoolResults,
+    /// nanoseconds per tick
+    period: f64,
+    /// of the timestamps, the bits above are garbage
+    mask: u64,
+    /// frame number and slot of the frames waiting for their results
+    pending: VecDeque<(u64, u32)>,
+    next_slot: u32,
+}
+
+impl GpuTimer {
+    /// None if the queue can't write timestamps
+    pub fn new(instance: &Instance, queue: Arc<Queue>) -> Option<GpuTimer> {
+        let bits = queue.family().timestamp_valid_bits()?;
+        let device = queue.device().clone();
+        let period = device.physical_device().limits().timestamp_period();
+        let pool = UnsafeQueryPool::new(device, QueryType::Timestamp, 2 * SLOTS).ok()?;
+        let loader = loader::auto_loader().ok()?;
+        let name = b"vkGetQueryPoolResults\0".as_ptr() as *const c_char;
+        // a core function, the loader always has it
+        let get_results = unsafe {
+            std::mem::transmute::<extern "system" fn(), GetQueryPoolResults>(
+                loader.get_instance_proc_addr(instance.internal_object(), name),
+            )
+        };
+        Some(GpuTimer {
+            queue,
+            pool: Arc::new(pool),
+            get_results,
+            period: f64::from(period),
+            mask: 1u64.checked_shl(bits).map_or(u64::MAX, |m| m - 1),
+            pending: VecDeque::new(),
+            next_slot: 0,
+        })
+    }
+
+    /// Commands to submit before those of frame number `frame`, and those to submit after
+    pub fn around(
+        &mut self,
+        frame: u64,
+    ) -> Result<(TimestampCommands, TimestampCommands), RenderError> {
+        if self.pending.len() == SLOTS as usize {
+            // lost, the GPU never got to it
+            self.pending.pop_front();
+        }
+        let slot = self.next_slot;
+        let pool = &self.pool;
+        let missing = || RenderError::Submit(format!("no timestamp query {} in the pool", slot));
+        let queries = pool.queries_range(2 * slot, 2).ok_or_else(missing)?;
+        let start_query = pool.query(2 * slot).ok_or_else(missing)?;
+        let end_query = pool.query(2 * slot + 1).ok_or_else(missing)?;
+        let error = |e: OomError| RenderError::Submit(e.to_string());
+        let start = self
+            .record(|builder| unsafe {
+                builder.reset_query_pool(queries);
+                let stages = PipelineStages {
+                    top_of_pipe: true,
+                    ..PipelineStages::none()
+                };
+                builder.write_timestamp(start_query, stages);
+            })
+            .map_err(error)?;
+        let end = self
+            .record(|builder| unsafe {
+                let stages = PipelineStages {
+                    bottom_of_pipe: true,
+                    ..PipelineStages::none()
+                };
+                builder.write_timestamp(end_query, stages);
+            })
+            .map_err(error)?;
+        self.next_slot = (slot + 1) % SLOTS;
+        self.pending.push_back((frame, slot));
+        Ok((start, end))
+    }
+
+    /// Frame numbers and GPU times in seconds of the frames measured since the last call
+    pub fn results(&mut self) -> Vec<(u64, f64)> {
+        let mut results = Vec::new();
+        while let Some(&(frame, slot)) = self.pending.front() {
+            let mut timestamps = [0u64; 2];
+            let status = unsafe {
+                (self.get_results)(
+                    self.pool.device().internal_object(),
+                    self.pool.internal_object(),
+                    2 * slot,
+                    2,
+                    std::mem::size_of_val(&timestamps),
+                    timestamps.as_mut_ptr() as *mut c_void,
+                    8,
+                    RESULT_64_BIT,
+                )
+            };
+            // the frames finish in order, if this one is not done the next ones aren't either
+            if status != SUCCESS {
+                break;
+            }
+            let ticks = timestamps[1].wrapping_sub(timestamps[0]) & self.mask;
+            results.push((frame, ticks as f64 * self.period * 1e-9));
+            self.pending.pop_front();
+        }
+        results
+    }
+
+    fn record(
+        &self,
+        commands: impl FnOnce(&mut UnsafeCommandBufferBuilder<StandardCommandPoolBuilder>),
+    ) -> Result<TimestampCommands, OomError> {
+        let device = self.queue.device();
+        let pool = Device::standard_command_pool(device, self.queue.family());
+        unsafe {
+            let mut builder =
+                UnsafeCommandBufferBuilder::new(&pool, Kind::primary(), Flags::OneTimeSubmit)?;
+            commands(&mut builder);
+            Ok(TimestampCommands {
+                inner: builder.build()?,
+                _pool: self.pool.clone(),
+            })
+        }
+    }
+}
+
+/// A command buffer writing timestamps, which `AutoCommandBufferBuilder` can't
+pub struct TimestampCommands {
+    inner: UnsafeCommandBuffer<StandardCommandPoolAlloc>,
+    /// kept alive until the GPU is done
+    _pool: Arc<UnsafeQueryPool>,
+}
+
+unsafe impl DeviceOwned for TimestampCommands {
+    fn device(&self) -> &Arc<Device> {
+        self.inner.device()
+    }
+}
+
+// It touches neither buffers nor images, so it has nothing to lock nor check
+unsafe impl CommandBuffer for TimestampCommands {
+    type PoolAlloc = StandardCommandPoolAlloc;
+
+    fn inner(&self) -> &UnsafeCommandBuffer<StandardCommandPoolAlloc> {
+        &self.inner
+    }
+
+    fn lock_submit(&self, _: &dyn GpuFuture, _: &Queue) -> Result<(), CommandBufferExecError> {
+        Ok(())
+    }
+
+    unsafe fn unlock(&self) {}
+
+    fn check_buffer_access(
+        &self,
+        _: &dyn BufferAccess,
+        _: bool,
+        _: &Queue,
+    ) -> Result<Option<(PipelineStages, AccessFlagBits)>, AccessCheckError> {
+        Err(AccessCheckError::Unknown)
+    }
+
+    fn check_image_access(
+        &self,
+        _: &dyn ImageAccess,
+        _: ImageLayout,
+        _: bool,
+        _: &Queue,
+    ) -> Result<Option<(PipelineStages, AccessFlagBits)>, AccessCheckError> {
+        Err(AccessCheckError::Unknown)
+    }
+}
@@ -0,0 +1,242 @@
+use crate::view::View;
+use crate::vk_render::{font, RenderError};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+use vulkano::buffer::CpuBufferPool;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, DynamicState};
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::DescriptorSet;
+use vulkano::device::{Device, Queue};
+use vulkano::format::Format;
+use vulkano::framebuffer::{RenderPassAbstract, Subpass};
+use vulkano::image::{Dimensions, ImmutableImage};
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::sampler::{Filter, MipmapMode, Sampler, SamplerAddressMode};
+use vulkano::sync::GpuFuture;
+
+/// Color of the text
+const TEXT: [f32; 4] = [1., 1., 1., 1.];
+/// Color of the box behind the text, translucent so that the fractal shows through
+const BACKGROUND: [f32; 4] = [0., 0., 0., 0.6];
+/// Window height, in pixels, for one pixel per texel of the font. Taller windows get bigger text
+const BASE_HEIGHT: u32 = 360;
+/// How often the frame time and rate are updated, in seconds
+const FPS_PERIOD: f64 = 0.5;
+
+#[derive(Default, Copy, Clone, Debug)]
+pub struct HudVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+impl_vertex!(HudVertex, position, uv, color);
+
+/// Frame time and rate, averaged over `FPS_PERIOD` so that they can be read
+struct FrameCounter {
+    since: Instant,
+    frames: u32,
+    /// in seconds
+    frame_time: f64,
+    fps: f64,
+}
+
+impl FrameCounter {
+    fn tick(&mut self) {
+        self.frames += 1;
+        let elapsed = self.since.elapsed().as_secs_f64();
+        if elapsed >= FPS_PERIOD {
+            self.frame_time = elapsed / f64::from(self.frames);
+            self.fps = f64::from(self.frames) / elapsed;
+            self.since = Instant::now();
+            self.frames = 0;
+        }
+    }
+}
+
+/// Text drawn over the fractal, in the second subpass: where we are and how fast it draws
+pub struct Hud {
+    pub visible: bool,
+    pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    vertices: CpuBufferPool<HudVertex>,
+    /// the font atlas and its sampler
+    set: Arc<dyn DescriptorSet + Send + Sync>,
+    counter: FrameCounter,
+}
+
+impl Hud {
+    /// Sets up the pipeline drawing in the second subpass of `render_pass`, uploading the font
+    pub fn new(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
+    ) -> Result<Hud, RenderError> {
+        let error = |e: &dyn fmt::Display| RenderError::Shader(format!("HUD: {}", e));
+        let [width, height] = font::atlas_size();
+        let (atlas, upload) = ImmutableImage::from_iter(
+            font::atlas().into_iter(),
+            Dimensions::Dim2d { width, height },
+            Format::R8Unorm,
+            queue,
+        )
+        .map_err(|e| error(&e))?;
+        upload
+            .then_signal_fence_and_flush()
+            .and_then(|future| future.wait(None))
+            .map_err(|e| error(&e))?;
+        // one texel per pixel or a whole multiple of it, so the nearest texel keeps the glyphs sharp
+        let sampler = Sampler::new(
+            device.clone(),
+            Filter::Nearest,
+            Filter::Nearest,
+            MipmapMode::Nearest,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            SamplerAddressMode::ClampToEdge,
+            0.0,
+            1.0,
+            0.0,
+            0.0,
+        )
+        .map_err(|e| error(&e))?;
+
+        let vs = crate::hud_vs::Shader::load(device.clone()).map_err(|e| error(&e))?;
+        let fs = crate::hud_fs::Shader::load(device.clone()).map_err(|e| error(&e))?;
+        let pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync> = Arc::new(
+            GraphicsPipeline::start()
+                .vertex_input_single_buffer::<HudVertex>()
+                .vertex_shader(vs.main_entry_point(), ())
+                .triangle_list()
+                .viewports_dynamic_scissors_irrelevant(1)
+                .fragment_shader(fs.main_entry_point(), ())
+                .blend_alpha_blending()
+                .render_pass(Subpass::from(render_pass, 1).ok_or_else(|| error(&"no subpass"))?)
+                .build(device.clone())
+                .map_err(|e| error(&e))?,
+        );
+        let set = Arc::new(
+            PersistentDescriptorSet::start(pipeline.clone(), 0)
+                .add_sampled_image(atlas, sampler)
+                .map_err(|e| error(&e))?
+                .build()
+                .map_err(|e| error(&e))?,
+        );
+
+        Ok(Hud {
+            visible: false,
+            pipeline,
+            vertices: CpuBufferPool::vertex_buffer(device),
+            set,
+            counter: FrameCounter {
+                since: Instant::now(),
+                frames: 0,
+                frame_time: 0.,
+                fps: 0.,
+            },
+        })
+    }
+
+    /// To be called once per frame, shown or not
+    pub fn tick(&mut self) {
+        self.counter.tick();
+    }
+
+    /// `gpu` being the time the GPU takes to draw a frame, in seconds
+    fn lines(&self, view: &View, gpu: Option<f64>) -> Vec<String> {
+        let [x, y] = view.camera.center;
+        let auto = if view.auto_iter { " (auto)" } else { "" };
+        let gpu = gpu.map_or(String::new(), |t| format!(", gpu {:.1} ms", t * 1000.));
+        vec![
+            format!("center     {}, {}", x, y),
+            format!("zoom       {:.3e}", view.camera.zoom),
+            format!("iterations {}{}", view.iterations(), auto),
+            format!(
+                "frame      {:.1} ms, {:.0} fps{}",
+                self.counter.frame_time * 1000.,
+                self.counter.fps,
+                gpu
+            ),
+        ]
+    }
+
+    /// Adds the draw of the HUD of `view` to the second subpass on images of `dimensions`,
+    /// `gpu` being the time the GPU takes to draw a frame, in seconds
+    pub fn draw(
+        &self,
+        builder: AutoCommandBufferBuilder,
+        view: &View,
+        gpu: Option<f64>,
+        dimensions: [u32; 2],
+        dynamic_state: &DynamicState,
+    ) -> Result<AutoCommandBufferBuilder, RenderError> {
+        let lines = self.lines(view, gpu);
+        let scale = (dimensions[1] / BASE_HEIGHT).max(1) as f32;
+        let advance = (font::GLYPH_WIDTH + 1) as f32 * scale;
+        let line_height = (font::GLYPH_HEIGHT + 3) as f32 * scale;
+        let margin = 4. * scale;
+        let padding = 3. * scale;
+        let columns = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+        let mut vertices = Vec::new();
+        // `position` and `size` in pixels
+        let mut quad = |position: [f32; 2], size: [f32; 2], glyph: u32, color: [f32; 4]| {
+            let to_device = |x: f32, y: f32| {
+                [
+                    2. * x / dimensions[0] as f32 - 1.,
+                    2. * y / dimensions[1] as f32 - 1.,
+                ]
+            };
+            let [[u0, v0], [u1, v1]] = font::uv(glyph);
+            let [x0, y0] = position;
+            let [x1, y1] = [x0 + size[0], y0 + size[1]];
+            let corner = |x, y, u, v| HudVertex {
+                position: to_device(x, y),
+                uv: [u, v],
+                color,
+            };
+            vertices.extend_from_slice(&[
+                corner(x0, y0, u0, v0),
+                corner(x0, y1, u0, v1),
+                corner(x1, y0, u1, v0),
+                corner(x1, y0, u1, v0),
+                corner(x1, y1, u1, v1),
+                corner(x0, y1, u0, v1),
+            ]);
+        };
+
+        quad(
+            [margin, margin],
+            [
+                columns as f32 * advance - scale + 2. * padding,
+                lines.len() as f32 * line_height - 3. * scale + 2. * padding,
+            ],
+            font::SOLID,
+            BACKGROUND,
+        );
+        let glyph_size = [
+            font::GLYPH_WIDTH as f32 * scale,
+            font::GLYPH_HEIGHT as f32 * scale,
+        ];
+        for (row, line) in lines.iter().enumerate() {
+            for (column, c) in line.chars().enumerate() {
+                if let Some(glyph) = font::glyph(c) {
+                    let x = margin + padding + column as f32 * advance;
+                    let y = margin + padding + row as f32 * line_height;
+                    quad([x, y], glyph_size, glyph, TEXT);
+                }
+            }
+        }
+
+        let error = |e: &dyn fmt::Display| RenderError::Submit(format!("HUD: {}", e));
+        let vertex_buffer = self.vertices.chunk(vertices).map_err(|e| error(&e))?;
+        builder
+            .draw(
+                self.pipeline.clone(),
+                dynamic_state,
+                vec![Arc::new(vertex_buffer)],
+                self.set.clone(),
+                (),
+            )
+            .map_err(|e| error(&e))
+    }
+}
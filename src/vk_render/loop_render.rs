@@ -1,15 +1,39 @@
+use crate::triple_buffer::Reader;
+use crate::view::View;
+use crate::vk_render::stats::FrameTimes;
 use crate::vk_render::*;
-use std::sync::{Arc, Mutex};
+use std::fmt;
+use std::sync::mpsc;
+use std::time::Instant;
 use vulkano::command_buffer::AutoCommandBufferBuilder;
-use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
 use vulkano::swapchain;
 use vulkano::swapchain::AcquireError;
 use vulkano::sync;
 use vulkano::sync::{FlushError, GpuFuture};
 
 impl Graphics {
-    pub fn loop_render(&mut self, uniform: Arc<Mutex<Uniform>>) {
-        let mut previous_frame_end = Box::new(sync::now(self.device.clone())) as Box<dyn GpuFuture>;
+    /// Draws `view` until `exit` is set, following the commands received on the way.
+    /// Stops early if the GPU can't draw anymore
+    pub fn loop_render(
+        &mut self,
+        view: Reader<View>,
+        commands: mpsc::Receiver<RenderCommand>,
+    ) -> Result<(), RenderError> {
+        let result = self.draw_frames(view, commands);
+        self.stats.finish();
+        result
+    }
+
+    fn draw_frames(
+        &mut self,
+        mut view: Reader<View>,
+        commands: mpsc::Receiver<RenderCommand>,
+    ) -> Result<(), RenderError> {
+        let submit_error = |e: &dyn fmt::Display| RenderError::Submit(e.to_string());
+        let mut previous_frame_end =
+            Box::new(sync::now(self.renderer.device.clone())) as Box<dyn GpuFuture>;
+        let mut frame = 0;
+        let mut frame_start = Instant::now();
 
         loop {
             // Calling this function polls various fences in order to determine what the GPU has
@@ -17,29 +41,30 @@ impl Graphics {
             previous_frame_end.cleanup_finished();
 
             if self.exit.load(Relaxed) {
-                return;
+                return Ok(());
             }
             if self.recreate_swapchain.load(Relaxed) {
-                self.resize();
+                self.resize()?;
                 self.recreate_swapchain.store(false, Relaxed);
             }
 
-            let uniform_read_window = *uniform.lock().unwrap();
-            let uniform_buffer_subbuffer =
-                { self.uniform_buffer.next(uniform_read_window).unwrap() };
-            drop(uniform_read_window);
-
-            let set = Arc::new(
-                PersistentDescriptorSet::start(self.pipeline.clone(), 0)
-                    .add_buffer(uniform_buffer_subbuffer)
-                    .unwrap()
-                    .build()
-                    .unwrap(),
-            );
+            let view = view.read();
+            for command in commands.try_iter() {
+                match command {
+                    RenderCommand::Palette(palette) => self.renderer.set_palette(&palette)?,
+                    RenderCommand::Screenshot { location, scale } => {
+                        self.screenshot(location, scale)
+                    }
+                    RenderCommand::ToggleHud => self.hud.visible = !self.hud.visible,
+                }
+            }
+            let aspect = self.dimensions[0] as f32 / self.dimensions[1] as f32;
+            let set = self.renderer.descriptor_set(&view, aspect)?;
 
             // Before we can draw on the output, we have to *acquire* an image from the swapchain
             //  the function will block if too many requests are sent,
             //  the optional param is a timer after which the function returns an error
+            let acquire_start = Instant::now();
             let (image_num, acquire_future) =
                 match swapchain::acquire_next_image(self.swapchain.clone(), None) {
                     Ok(r) => r,
@@ -47,37 +72,75 @@ impl Graphics {
                         self.recreate_swapchain.store(true, Relaxed);
                         continue;
                     }
-                    Err(err) => panic!("{:?}", err),
+                    Err(err) => return Err(RenderError::Swapchain(err.to_string())),
                 };
+            let acquire = acquire_start.elapsed();
+            if let Some(timer) = &mut self.gpu_timer {
+                for (frame, seconds) in timer.results() {
+                    self.stats.gpu_time(frame, seconds);
+                }
+            }
 
             // color to clear the framebuffer with
             let clear_values = vec![[0.0, 0.0, 0.0, 1.0].into()];
 
-            let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
-                self.device.clone(),
-                self.queue.family(),
+            self.hud.tick();
+            let mut command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
+                self.renderer.device.clone(),
+                self.renderer.queue.family(),
             )
-            .unwrap()
+            .map_err(|e| submit_error(&e))?
             .begin_render_pass(self.framebuffers[image_num].clone(), false, clear_values)
-            .unwrap()
+            .map_err(|e| submit_error(&e))?
             .draw(
-                self.pipeline.clone(),
+                self.renderer.pipeline.clone(),
                 &self.dynamic_state,
-                vec![self.vertex_buffer.clone()], // WHY ??
-                set.clone(),
+                vec![self.renderer.vertex_buffer.clone()], // WHY ??
+                set,
                 (),
             )
-            .unwrap()
-            .end_render_pass()
-            .unwrap()
-            .build()
-            .unwrap();
+            .map_err(|e| submit_error(&e))?
+            .next_subpass(false)
+            .map_err(|e| submit_error(&e))?;
+            if self.hud.visible {
+                command_buffer = self.hud.draw(
+                    command_buffer,
+                    &view,
+                    self.stats.mean_gpu(),
+                    self.dimensions,
+                    &self.dynamic_state,
+                )?;
+            }
+            let command_buffer = command_buffer
+                .end_render_pass()
+                .map_err(|e| submit_error(&e))?
+                .build()
+                .map_err(|e| submit_error(&e))?;
 
-            let future = previous_frame_end
-                .join(acquire_future)
-                .then_execute(self.queue.clone(), command_buffer)
-                .unwrap()
-                .then_swapchain_present(self.queue.clone(), self.swapchain.clone(), image_num)
+            let present_start = Instant::now();
+            let queue = self.renderer.queue.clone();
+            let future = Box::new(previous_frame_end.join(acquire_future)) as Box<dyn GpuFuture>;
+            // between timestamps if the GPU can write them
+            let timestamps = match &mut self.gpu_timer {
+                Some(timer) => Some(timer.around(frame)?),
+                None => None,
+            };
+            let future = match timestamps {
+                Some((start, end)) => Box::new(
+                    future
+                        .then_execute(queue.clone(), start)
+                        .and_then(|f| f.then_execute(queue.clone(), command_buffer))
+                        .and_then(|f| f.then_execute(queue.clone(), end))
+                        .map_err(|e| submit_error(&e))?,
+                ) as Box<dyn GpuFuture>,
+                None => Box::new(
+                    future
+                        .then_execute(queue.clone(), command_buffer)
+                        .map_err(|e| submit_error(&e))?,
+                ),
+            };
+            let future = future
+                .then_swapchain_present(queue, self.swapchain.clone(), image_num)
                 .then_signal_fence_and_flush();
 
             match future {
@@ -86,13 +149,29 @@ impl Graphics {
                 }
                 Err(FlushError::OutOfDate) => {
                     self.recreate_swapchain.store(true, Relaxed);
-                    previous_frame_end = Box::new(sync::now(self.device.clone())) as Box<_>;
+                    previous_frame_end =
+                        Box::new(sync::now(self.renderer.device.clone())) as Box<_>;
+                }
+                Err(FlushError::DeviceLost) => {
+                    return Err(submit_error(&FlushError::DeviceLost));
                 }
                 Err(e) => {
-                    println!("{:?}", e);
-                    previous_frame_end = Box::new(sync::now(self.device.clone())) as Box<_>;
+                    eprintln!("Could not draw a frame: {}", e);
+                    previous_frame_end =
+                        Box::new(sync::now(self.renderer.device.clone())) as Box<_>;
                 }
             }
+
+            let now = Instant::now();
+            let times = FrameTimes {
+                frame: now.duration_since(frame_start).as_secs_f64(),
+                acquire: acquire.as_secs_f64(),
+                present: now.duration_since(present_start).as_secs_f64(),
+                gpu: None,
+            };
+            self.stats.record(frame, times);
+            frame += 1;
+            frame_start = now;
         }
     }
 }
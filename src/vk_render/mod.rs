@@ -4,46 +4,133 @@ use vulkano::buffer::{CpuAccessibleBuffer, CpuBufferPool};
 use vulkano::command_buffer::DynamicState;
 use vulkano::device::Device;
 use vulkano::device::Queue;
+use vulkano::format::Format;
 use vulkano::framebuffer::{Framebuffer, FramebufferAbstract, RenderPassAbstract};
 use vulkano::image::SwapchainImage;
 use vulkano::pipeline::viewport::Viewport;
 use vulkano::pipeline::GraphicsPipelineAbstract;
-use vulkano::swapchain::Swapchain;
+use vulkano::swapchain::{PresentMode, Swapchain};
+
+use crate::location::Location;
+use crate::palette::{Palette, PALETTE_SIZE};
+use crate::perturbation::ReferenceOrbit;
+pub use error::RenderError;
+use gpu_timer::GpuTimer;
+use hud::Hud;
+use stats::FrameStats;
 
 #[derive(Default, Copy, Clone, Debug)]
 pub struct Vertex {
     position: [f32; 2],
 }
+impl_vertex!(Vertex, position);
 
+/// Must match the `Data` uniform block of `shaders/uniforms.glsl` (std140)
+#[repr(C)]
 #[derive(Clone, Copy)]
 pub struct Uniform {
     pub zoom: f32,
+    pub aspect: f32,
+    /// see `camera::Camera::rotation`
+    pub rotation: f32,
     pub position_x: f32,
     pub position_y: f32,
+    /// what position_{x,y} lost when rounded to f32, for the double precision shaders
+    pub pos_x_lo: f32,
+    pub pos_y_lo: f32,
+    /// 0 draws the Mandelbrot set, 1 the Julia set of (julia_x, julia_y)
+    pub julia: u32,
+    pub julia_x: f32,
+    pub julia_y: f32,
+    /// a `fractal::Formula`
+    pub formula: u32,
+    /// exponent of `Formula::Multibrot`
+    pub power: u32,
+    /// a `view::Precision`, with `Perturbation` the pixels are offsets to the orbit in binding 1
+    pub precision_mode: u32,
+    /// number of points in the orbit
+    pub ref_len: u32,
+    /// view center - orbit center
+    pub ref_offset_x: f32,
+    pub ref_offset_y: f32,
+    pub max_iter: u32,
+    /// a `coloring::Coloring`
+    pub coloring: u32,
+    /// position in the palette of iteration 0, 1 being a whole turn
+    pub palette_offset: f32,
+    /// iterations for the palette to repeat
+    pub palette_period: f32,
 }
 
-pub struct Graphics {
-    pub surface: Arc<vulkano::swapchain::Surface<winit::Window>>,
+/// How the window is opened
+#[derive(Clone, Copy, Debug)]
+pub struct WindowOptions {
+    /// inner size in logical pixels, winit chooses one if None
+    pub size: Option<[u32; 2]>,
+    /// on the primary monitor
+    pub fullscreen: bool,
+    /// falls back to Fifo, the only one always available, if the surface does not support it
+    pub present_mode: PresentMode,
+}
 
-    pub instance: Arc<vulkano::instance::Instance>,
-    pub device_ext: vulkano::device::DeviceExtensions,
+/// Sent to the render thread
+pub enum RenderCommand {
+    /// switch to this palette
+    Palette(Palette),
+    /// save `location` as a PNG, at `scale` times the window resolution
+    Screenshot { location: Location, scale: u32 },
+    /// show or hide the HUD
+    ToggleHud,
+}
+
+/// What draws the fractal, to the window or to offscreen images
+pub struct Renderer {
     pub device: Arc<Device>,
     pub queue: Arc<Queue>,
-    pub swapchain: Arc<Swapchain<winit::Window>>,
 
     pub vertex_buffer: Arc<CpuAccessibleBuffer<[Vertex]>>,
     pub uniform_buffer: CpuBufferPool<Uniform>,
+    /// reference orbit for the deep zooms, see `crate::perturbation`
+    pub reference: Option<ReferenceOrbit>,
+    pub orbit_buffer: Arc<CpuAccessibleBuffer<[[f32; 2]]>>,
+    pub palette_buffer: Arc<CpuAccessibleBuffer<[[f32; 4]; PALETTE_SIZE]>>,
 
+    /// draws to images of `format`
     pub render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
     pub pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync>,
+    pub format: Format,
+}
+
+pub struct Graphics {
+    pub surface: Arc<vulkano::swapchain::Surface<winit::Window>>,
+
+    #[allow(dead_code)]
+    pub instance: Arc<vulkano::instance::Instance>,
+    #[allow(dead_code)]
+    pub device_ext: vulkano::device::DeviceExtensions,
+    pub swapchain: Arc<Swapchain<winit::Window>>,
+    /// of the swapchain images, in pixels
+    pub dimensions: [u32; 2],
+
+    pub renderer: Renderer,
 
     pub dynamic_state: DynamicState,
     pub framebuffers: Vec<Arc<dyn FramebufferAbstract + Send + Sync>>,
 
     pub recreate_swapchain: Arc<AtomicBool>,
     pub exit: Arc<AtomicBool>,
-    // camera
-    // fps_counter
+
+    pub hud: Hud,
+    /// None if the GPU can't measure its time
+    pub gpu_timer: Option<GpuTimer>,
+    pub stats: FrameStats,
+}
+
+/// Inner size of `window` in pixels, None if it is closed
+fn window_dimensions(window: &winit::Window) -> Option<[u32; 2]> {
+    let dims = window.get_inner_size()?;
+    let d: (u32, u32) = dims.to_physical(window.get_hidpi_factor()).into();
+    Some([d.0, d.1])
 }
 
 /// This method is called once during initialization, then again whenever the window is resized
@@ -51,7 +138,7 @@ fn window_size_dependent_setup(
     images: &[Arc<SwapchainImage<winit::Window>>],
     render_pass: Arc<dyn RenderPassAbstract + Send + Sync>,
     dynamic_state: &mut DynamicState,
-) -> Vec<Arc<dyn FramebufferAbstract + Send + Sync>> {
+) -> Result<Vec<Arc<dyn FramebufferAbstract + Send + Sync>>, RenderError> {
     let dimensions = images[0].dimensions();
     let viewport = Viewport {
         origin: [0.0, 0.0],
@@ -63,17 +150,23 @@ fn window_size_dependent_setup(
     images
         .iter()
         .map(|image| {
-            Arc::new(
-                Framebuffer::start(render_pass.clone())
-                    .add(image.clone())
-                    .unwrap()
-                    .build()
-                    .unwrap(),
-            ) as Arc<dyn FramebufferAbstract + Send + Sync>
+            let framebuffer = Framebuffer::start(render_pass.clone())
+                .add(image.clone())
+                .and_then(|framebuffer| framebuffer.build())
+                .map_err(|e| RenderError::Swapchain(e.to_string()))?;
+            Ok(Arc::new(framebuffer) as Arc<dyn FramebufferAbstract + Send + Sync>)
         })
-        .collect::<Vec<_>>()
+        .collect()
 }
 
+pub mod device;
+pub mod error;
+pub mod font;
+pub mod gpu_timer;
+pub mod hud;
 pub mod loop_render;
 pub mod new;
+pub mod renderer;
 pub mod resize;
+pub mod screenshot;
+pub mod stats;
@@ -1,65 +1,92 @@
+use crate::palette::Palette;
+use crate::vk_render::device::{self, DeviceChoice};
 use crate::vk_render::*;
 use std::sync::Arc;
-use vulkano::buffer::CpuBufferPool;
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
 use vulkano::command_buffer::DynamicState;
 use vulkano::device::{Device, DeviceExtensions};
-use vulkano::framebuffer::Subpass;
-use vulkano::instance::{Instance, PhysicalDevice};
-use vulkano::pipeline::GraphicsPipeline;
+use vulkano::instance::Instance;
 use vulkano::swapchain::{PresentMode, SurfaceTransform, Swapchain};
 use vulkano_win::VkSurfaceBuild;
+use winit::dpi::LogicalSize;
 use winit::WindowBuilder;
 
 impl Graphics {
-    pub fn new(events_loop: &winit::EventsLoop) -> Graphics {
+    /// Opens the window and sets up the GPU to draw in it
+    pub fn new(
+        events_loop: &winit::EventsLoop,
+        palette: &Palette,
+        options: &WindowOptions,
+        device: &DeviceChoice,
+    ) -> Result<Graphics, RenderError> {
         let instance = {
             let extensions = vulkano_win::required_extensions();
-            Instance::new(None, &extensions, None).unwrap()
+            Instance::new(None, &extensions, None)
+                .map_err(|e| RenderError::Instance(e.to_string()))?
         };
-        let surface = WindowBuilder::new()
+        let mut window = WindowBuilder::new();
+        if let Some([width, height]) = options.size {
+            window = window.with_dimensions(LogicalSize::new(width.into(), height.into()));
+        }
+        if options.fullscreen {
+            window = window.with_fullscreen(Some(events_loop.get_primary_monitor()));
+        }
+        let surface = window
             .build_vk_surface(events_loop, instance.clone())
-            .unwrap();
+            .map_err(|e| RenderError::Window(e.to_string()))?;
 
-        let _physical = PhysicalDevice::enumerate(&instance).next().unwrap();
+        let (physical, queue_family) =
+            device::select(&instance, device, "that can draw to the window", |q| {
+                q.supports_graphics() && surface.is_supported(q).unwrap_or(false)
+            })
+            .map_err(RenderError::Device)?;
         println!(
             "Using device: {} (type: {:?})",
-            _physical.name(),
-            _physical.ty()
+            physical.name(),
+            physical.ty()
         );
 
         let device_ext = DeviceExtensions {
             khr_swapchain: true,
             ..DeviceExtensions::none()
         };
-        let (device, mut _queues) = {
-            let queue_family = _physical
-                .queue_families()
-                .find(|&q| q.supports_graphics() && surface.is_supported(q).unwrap_or(false))
-                .unwrap();
-            Device::new(
-                _physical,
-                _physical.supported_features(),
-                &device_ext,
-                [(queue_family, 0.5)].iter().cloned(),
-            )
-            .unwrap()
-        };
-
-        let queue = _queues.next().unwrap(); // we use only one queue, so we just retrieve the first
+        let (device, mut _queues) = Device::new(
+            physical,
+            physical.supported_features(),
+            &device_ext,
+            [(queue_family, 0.5)].iter().cloned(),
+        )
+        .map_err(|e| RenderError::Device(e.to_string()))?;
+
+        // we use only one queue, so we just retrieve the first
+        let queue = _queues
+            .next()
+            .ok_or_else(|| RenderError::Device("no queue".to_owned()))?;
         let (swapchain, _images) = {
-            let caps = surface.capabilities(_physical).unwrap();
+            let caps = surface
+                .capabilities(physical)
+                .map_err(|e| RenderError::Swapchain(e.to_string()))?;
             let usage = caps.supported_usage_flags;
-            let alpha = caps.supported_composite_alpha.iter().next().unwrap();
-            let internal_format = caps.supported_formats[0].0;
-            let window = surface.window();
-            let initial_dimensions: [u32; 2] = match window.get_inner_size() {
-                Some(dims) => {
-                    let d: (u32, u32) = dims.to_physical(window.get_hidpi_factor()).into();
-                    [d.0, d.1]
-                }
-                None => panic!("Could not get inner window dimensions"),
+            let alpha = caps
+                .supported_composite_alpha
+                .iter()
+                .next()
+                .ok_or_else(|| RenderError::Swapchain("no composite alpha mode".to_owned()))?;
+            let internal_format = caps
+                .supported_formats
+                .first()
+                .ok_or_else(|| RenderError::Swapchain("no surface format".to_owned()))?
+                .0;
+            let present_mode = if caps.present_modes.supports(options.present_mode) {
+                options.present_mode
+            } else {
+                println!(
+                    "Present mode {:?} not supported, using Fifo",
+                    options.present_mode
+                );
+                PresentMode::Fifo
             };
+            let initial_dimensions = window_dimensions(surface.window())
+                .ok_or_else(|| RenderError::Window("the window closed".to_owned()))?;
             Swapchain::new(
                 device.clone(),
                 surface.clone(),
@@ -71,75 +98,23 @@ impl Graphics {
                 &queue,
                 SurfaceTransform::Identity,
                 alpha,
-                PresentMode::Fifo,
+                present_mode,
                 true,
                 None,
             )
-            .unwrap()
-        };
-
-        let vertex_buffer = {
-            impl_vertex!(Vertex, position);
-
-            CpuAccessibleBuffer::from_iter(
-                device.clone(),
-                BufferUsage::all(),
-                [
-                    Vertex {
-                        position: [-1., -1.],
-                    },
-                    Vertex {
-                        position: [-1., 1.],
-                    },
-                    Vertex {
-                        position: [1., -1.],
-                    },
-                    Vertex {
-                        position: [1., -1.],
-                    },
-                    Vertex { position: [1., 1.] },
-                    Vertex {
-                        position: [-1., 1.],
-                    },
-                ]
-                .iter()
-                .cloned(),
-            )
-            .unwrap()
+            .map_err(|e| RenderError::Swapchain(e.to_string()))?
         };
 
-        let uniform_buffer = CpuBufferPool::uniform_buffer(device.clone());
-
-        let render_pass = Arc::new(
-            single_pass_renderpass!(     // describes where the output of the graphics pipeline will go
-                device.clone(),
-                attachments: {
-                    color: {                            // custom name we give to the first and only attachment
-                        load: Clear,                    // clear the content of this attachment at the start of the drawing
-                        store: Store,                   // store the output of the draw in the actual image
-                        format: swapchain.format(),     // set the format of the image as the same as the swapchain
-                        samples: 1,
-                    }
-                },
-                pass: { color: [color], depth_stencil: {} }
-            )
-            .unwrap(),
-        );
-
-        let vs = crate::vs::Shader::load(device.clone()).unwrap();
-        let fs = crate::fs::Shader::load(device.clone()).unwrap();
-
-        let pipeline = Arc::new(
-            GraphicsPipeline::start()
-                .vertex_input_single_buffer::<Vertex>()
-                .vertex_shader(vs.main_entry_point(), ())
-                .triangle_list()
-                .viewports_dynamic_scissors_irrelevant(1) // Use a resizable viewport set to draw over the entire window
-                .fragment_shader(fs.main_entry_point(), ())
-                .render_pass(Subpass::from(render_pass.clone(), 0).unwrap())
-                .build(device.clone())
-                .unwrap(),
-        );
+        let renderer = Renderer::new(device, queue, swapchain.format(), palette)?;
+        let hud = Hud::new(
+            renderer.device.clone(),
+            renderer.queue.clone(),
+            renderer.render_pass.clone(),
+        )?;
+        let gpu_timer = GpuTimer::new(&instance, renderer.queue.clone());
+        if gpu_timer.is_none() {
+            println!("GPU times: not supported");
+        }
 
         let mut dynamic_state = DynamicState {
             line_width: None,
@@ -147,31 +122,33 @@ impl Graphics {
             scissors: None,
         };
 
-        let framebuffers =
-            window_size_dependent_setup(&_images, render_pass.clone(), &mut dynamic_state);
+        let framebuffers = window_size_dependent_setup(
+            &_images,
+            renderer.render_pass.clone(),
+            &mut dynamic_state,
+        )?;
         let recreate_swapchain = Arc::new(AtomicBool::new(false));
         let exit = Arc::new(AtomicBool::new(false));
 
-        Graphics {
+        Ok(Graphics {
             surface,
 
             instance,
             device_ext,
-            device,
-            queue,
+            dimensions: swapchain.dimensions(),
             swapchain,
 
-            vertex_buffer,
-            uniform_buffer,
-
-            render_pass,
-            pipeline,
+            renderer,
 
             dynamic_state,
             framebuffers,
 
             recreate_swapchain,
             exit,
-        }
+
+            hud,
+            gpu_timer,
+            stats: FrameStats::new(),
+        })
     }
 }
@@ -0,0 +1,356 @@
+use crate::palette::Palette;
+use crate::perturbation::ReferenceOrbit;
+use crate::view::{Precision, View};
+use crate::vk_render::device::{self, DeviceChoice};
+use crate::vk_render::*;
+use std::fmt;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer, CpuBufferPool};
+use vulkano::command_buffer::{
+    AutoCommandBuffer, AutoCommandBufferBuilder, CommandBufferExecFuture, DynamicState,
+};
+use vulkano::descriptor::descriptor_set::PersistentDescriptorSet;
+use vulkano::descriptor::DescriptorSet;
+use vulkano::device::{Device, DeviceExtensions};
+use vulkano::format::Format;
+use vulkano::framebuffer::Subpass;
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::instance::{Instance, InstanceExtensions};
+use vulkano::memory::DeviceMemoryAllocError;
+use vulkano::pipeline::{GraphicsPipeline, GraphicsPipelineAbstract};
+use vulkano::sync::{FenceSignalFuture, GpuFuture, NowFuture};
+
+impl Renderer {
+    /// Sets up the pipeline drawing to images of `format`
+    pub fn new(
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        format: Format,
+        palette: &Palette,
+    ) -> Result<Renderer, RenderError> {
+        let memory_error = |e: DeviceMemoryAllocError| RenderError::Submit(e.to_string());
+        let shader_error = |e: &dyn fmt::Display| RenderError::Shader(e.to_string());
+        let vertex_buffer = {
+            CpuAccessibleBuffer::from_iter(
+                device.clone(),
+                BufferUsage::all(),
+                [
+                    Vertex {
+                        position: [-1., -1.],
+                    },
+                    Vertex {
+                        position: [-1., 1.],
+                    },
+                    Vertex {
+                        position: [1., -1.],
+                    },
+                    Vertex {
+                        position: [1., -1.],
+                    },
+                    Vertex { position: [1., 1.] },
+                    Vertex {
+                        position: [-1., 1.],
+                    },
+                ]
+                .iter()
+                .cloned(),
+            )
+            .map_err(memory_error)?
+        };
+
+        let uniform_buffer = CpuBufferPool::uniform_buffer(device.clone());
+        // placeholder until we zoom deep enough to need a reference orbit
+        let orbit_buffer = CpuAccessibleBuffer::from_iter(
+            device.clone(),
+            BufferUsage::all(),
+            [[0f32; 2]].iter().cloned(),
+        )
+        .map_err(memory_error)?;
+        let palette_buffer = CpuAccessibleBuffer::from_data(
+            device.clone(),
+            BufferUsage::uniform_buffer(),
+            palette.texels(),
+        )
+        .map_err(memory_error)?;
+
+        let render_pass = Arc::new(
+            ordered_passes_renderpass!(  // describes where the output of the graphics pipelines will go
+                device.clone(),
+                attachments: {
+                    color: {                            // custom name we give to the first and only attachment
+                        load: Clear,                    // clear the content of this attachment at the start of the drawing
+                        store: Store,                   // store the output of the draw in the actual image
+                        format: format,                 // set the format of the image as the same as the target
+                        samples: 1,
+                    }
+                },
+                passes: [
+                    { color: [color], depth_stencil: {}, input: [] },   // the fractal
+                    { color: [color], depth_stencil: {}, input: [] }    // the HUD, blended over it
+                ]
+            )
+            .map_err(|e| shader_error(&e))?,
+        );
+
+        let vs = crate::vs::Shader::load(device.clone()).map_err(|e| shader_error(&e))?;
+        let pipeline = GraphicsPipeline::start()
+            .vertex_input_single_buffer::<Vertex>()
+            .vertex_shader(vs.main_entry_point(), ())
+            .triangle_list()
+            .viewports_dynamic_scissors_irrelevant(1) // Use a resizable viewport set to draw over the entire window
+            .render_pass(
+                Subpass::from(render_pass.clone(), 0)
+                    .ok_or_else(|| RenderError::Shader("no subpass to draw in".to_owned()))?,
+            );
+        // Same shaders, but without shader_f3264 (shaderFloat64) the double precision is emulated
+        let pipeline: Arc<dyn GraphicsPipelineAbstract + Send + Sync> =
+            if device.enabled_features().shader_f3264 {
+                println!("Double precision: native");
+                let fs =
+                    crate::fs_double::Shader::load(device.clone()).map_err(|e| shader_error(&e))?;
+                Arc::new(
+                    pipeline
+                        .fragment_shader(fs.main_entry_point(), ())
+                        .build(device.clone())
+                        .map_err(|e| shader_error(&e))?,
+                )
+            } else {
+                println!("Double precision: emulated");
+                let fs = crate::fs::Shader::load(device.clone()).map_err(|e| shader_error(&e))?;
+                Arc::new(
+                    pipeline
+                        .fragment_shader(fs.main_entry_point(), ())
+                        .build(device.clone())
+                        .map_err(|e| shader_error(&e))?,
+                )
+            };
+
+        Ok(Renderer {
+            device,
+            queue,
+
+            vertex_buffer,
+            uniform_buffer,
+            reference: None,
+            orbit_buffer,
+            palette_buffer,
+
+            render_pass,
+            pipeline,
+            format,
+        })
+    }
+
+    /// A renderer without window, for the offline renders
+    pub fn headless(device: &DeviceChoice, palette: &Palette) -> Result<Renderer, RenderError> {
+        let instance = Instance::new(None, &InstanceExtensions::none(), None)
+            .map_err(|e| RenderError::Instance(e.to_string()))?;
+        let (physical, queue_family) = device::select(&instance, device, "that can draw", |q| {
+            q.supports_graphics()
+        })
+        .map_err(RenderError::Device)?;
+        println!(
+            "Using device: {} (type: {:?})",
+            physical.name(),
+            physical.ty()
+        );
+        let (device, mut queues) = Device::new(
+            physical,
+            physical.supported_features(),
+            &DeviceExtensions::none(),
+            [(queue_family, 0.5)].iter().cloned(),
+        )
+        .map_err(|e| RenderError::Device(e.to_string()))?;
+        let queue = queues
+            .next()
+            .ok_or_else(|| RenderError::Device("no queue".to_owned()))?;
+        // like `cpu_render`, the colors are written as they are
+        Renderer::new(device, queue, Format::R8G8B8A8Unorm, palette)
+    }
+
+    pub fn set_palette(&mut self, palette: &Palette) -> Result<(), RenderError> {
+        self.palette_buffer = CpuAccessibleBuffer::from_data(
+            self.device.clone(),
+            BufferUsage::uniform_buffer(),
+            palette.texels(),
+        )
+        .map_err(|e| RenderError::Submit(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Bindings of the fractal shaders to draw `view` in a `aspect` (width / height) image,
+    /// computing a new reference orbit if the perturbation needs one
+    pub fn descriptor_set(
+        &mut self,
+        view: &View,
+        aspect: f32,
+    ) -> Result<Arc<dyn DescriptorSet + Send + Sync>, RenderError> {
+        let error = |e: &dyn fmt::Display| RenderError::Submit(e.to_string());
+        let perturbation = view.precision() == Precision::Perturbation;
+        if perturbation && !self.reference.as_ref().is_some_and(|r| r.fits(view)) {
+            let reference = ReferenceOrbit::compute(view);
+            self.orbit_buffer = CpuAccessibleBuffer::from_iter(
+                self.device.clone(),
+                BufferUsage::all(),
+                reference.points.iter().cloned(),
+            )
+            .map_err(|e| error(&e))?;
+            self.reference = Some(reference);
+        }
+        let reference = self.reference.as_ref().filter(|_| perturbation);
+        let uniform_buffer_subbuffer = self
+            .uniform_buffer
+            .next(view.uniform(reference, aspect))
+            .map_err(|e| error(&e))?;
+
+        let set = PersistentDescriptorSet::start(self.pipeline.clone(), 0)
+            .add_buffer(uniform_buffer_subbuffer)
+            .and_then(|set| set.add_buffer(self.orbit_buffer.clone()))
+            .and_then(|set| set.add_buffer(self.palette_buffer.clone()))
+            .map_err(|e| error(&e))?
+            .build()
+            .map_err(|e| error(&e))?;
+        Ok(Arc::new(set))
+    }
+
+    /// Draws `view` in a `width` x `height` image and reads it back as RGBA8 rows, top row first
+    pub fn render_offscreen(
+        &mut self,
+        view: &View,
+        width: u32,
+        height: u32,
+    ) -> Result<Vec<u8>, RenderError> {
+        self.start_offscreen(view, width, height)?.wait()
+    }
+
+    /// Biggest image the GPU can draw at once
+    pub fn max_offscreen_size(&self) -> [u32; 2] {
+        let limits = self.device.physical_device().limits();
+        let max_image = limits.max_image_dimension_2d();
+        [
+            limits.max_framebuffer_width().min(max_image),
+            limits.max_framebuffer_height().min(max_image),
+        ]
+    }
+
+    /// Submits the draw of `view` in a `width` x `height` image and its copy to memory,
+    /// without waiting for the GPU
+    pub fn start_offscreen(
+        &mut self,
+        view: &View,
+        width: u32,
+        height: u32,
+    ) -> Result<Readback, RenderError> {
+        let error = |e: &dyn fmt::Display| RenderError::Submit(e.to_string());
+        let max = self.max_offscreen_size();
+        if width > max[0] || height > max[1] {
+            return Err(RenderError::Submit(format!(
+                "{}x{} is bigger than the {}x{} the GPU can draw at once",
+                width, height, max[0], max[1]
+            )));
+        }
+        let bgra = match self.format {
+            Format::B8G8R8A8Unorm | Format::B8G8R8A8Srgb => true,
+            Format::R8G8B8A8Unorm | Format::R8G8B8A8Srgb => false,
+            _ => {
+                return Err(RenderError::Device(format!(
+                    "can't read back {:?} images",
+                    self.format
+                )))
+            }
+        };
+
+        let usage = ImageUsage {
+            transfer_source: true,
+            color_attachment: true,
+            ..ImageUsage::none()
+        };
+        let image =
+            AttachmentImage::with_usage(self.device.clone(), [width, height], self.format, usage)
+                .map_err(|e| error(&e))?;
+        let framebuffer = Arc::new(
+            Framebuffer::start(self.render_pass.clone())
+                .add(image.clone())
+                .and_then(|framebuffer| framebuffer.build())
+                .map_err(|e| error(&e))?,
+        );
+        // the copy overwrites all of it
+        let buffer = unsafe {
+            CpuAccessibleBuffer::uninitialized_array(
+                self.device.clone(),
+                width as usize * height as usize * 4,
+                BufferUsage::transfer_destination(),
+            )
+        }
+        .map_err(|e| error(&e))?;
+
+        let dynamic_state = DynamicState {
+            line_width: None,
+            viewports: Some(vec![Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [width as f32, height as f32],
+                depth_range: 0.0..1.0,
+            }]),
+            scissors: None,
+        };
+        let set = self.descriptor_set(view, width as f32 / height as f32)?;
+        let command_buffer = AutoCommandBufferBuilder::primary_one_time_submit(
+            self.device.clone(),
+            self.queue.family(),
+        )
+        .map_err(|e| error(&e))?
+        .begin_render_pass(framebuffer, false, vec![[0.0, 0.0, 0.0, 1.0].into()])
+        .map_err(|e| error(&e))?
+        .draw(
+            self.pipeline.clone(),
+            &dynamic_state,
+            vec![self.vertex_buffer.clone()],
+            set,
+            (),
+        )
+        .map_err(|e| error(&e))?
+        // no HUD on the images
+        .next_subpass(false)
+        .map_err(|e| error(&e))?
+        .end_render_pass()
+        .map_err(|e| error(&e))?
+        .copy_image_to_buffer(image, buffer.clone())
+        .map_err(|e| error(&e))?
+        .build()
+        .map_err(|e| error(&e))?;
+
+        let future = vulkano::sync::now(self.device.clone())
+            .then_execute(self.queue.clone(), command_buffer)
+            .map_err(|e| error(&e))?
+            .then_signal_fence_and_flush()
+            .map_err(|e| error(&e))?;
+        Ok(Readback {
+            future,
+            buffer,
+            bgra,
+        })
+    }
+}
+
+/// An image the GPU is drawing, see `Renderer::start_offscreen`
+pub struct Readback {
+    future: FenceSignalFuture<CommandBufferExecFuture<NowFuture, AutoCommandBuffer>>,
+    buffer: Arc<CpuAccessibleBuffer<[u8]>>,
+    bgra: bool,
+}
+
+impl Readback {
+    /// Waits for the GPU to be done, then reads the image as RGBA8 rows, top row first
+    pub fn wait(self) -> Result<Vec<u8>, RenderError> {
+        let error = |e: &dyn fmt::Display| RenderError::Submit(e.to_string());
+        self.future.wait(None).map_err(|e| error(&e))?;
+        let mut pixels = self.buffer.read().map_err(|e| error(&e))?.to_vec();
+        for pixel in pixels.chunks_exact_mut(4) {
+            if self.bgra {
+                pixel.swap(0, 2);
+            }
+            pixel[3] = 255;
+        }
+        Ok(pixels)
+    }
+}
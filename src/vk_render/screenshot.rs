@@ -0,0 +1,121 @@
+use crate::location::Location;
+use crate::png_io::encode_png;
+use crate::vk_render::*;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+use std::time::SystemTime;
+
+impl Graphics {
+    /// Renders `location` offscreen at `scale` times the window resolution, with the
+    /// pipeline of the window, and writes it to a timestamped PNG in the current directory
+    pub fn screenshot(&mut self, location: Location, scale: u32) {
+        let size = match self.screenshot_size(scale) {
+            Ok(size) => size,
+            Err(e) => return eprintln!("Could not take the screenshot: {}", e),
+        };
+        let [width, height] = size;
+        match self.renderer.start_offscreen(&location.view, width, height) {
+            Ok(readback) => {
+                // reading back and encoding a big image take a while, don't stall the rendering
+                // meanwhile
+                thread::spawn(move || {
+                    let pixels = match readback.wait() {
+                        Ok(pixels) => pixels,
+                        Err(e) => {
+                            eprintln!("Could not take the screenshot: {}", e);
+                            return;
+                        }
+                    };
+                    let (file, path) = match create_screenshot_file() {
+                        Ok(created) => created,
+                        Err(e) => return eprintln!("Could not create the screenshot: {}", e),
+                    };
+                    match encode_png(file, width, height, &pixels, &location) {
+                        Ok(()) => println!("Screenshot saved to {}", path.display()),
+                        Err(e) => {
+                            eprintln!("Could not write {}: {}", path.display(), e);
+                            fs::remove_file(&path).ok();
+                        }
+                    }
+                });
+            }
+            Err(e) => eprintln!("Could not take the screenshot: {}", e),
+        }
+    }
+
+    /// The window's size times `scale`, if the GPU can draw that
+    fn screenshot_size(&self, scale: u32) -> Result<[u32; 2], RenderError> {
+        let too_big = || {
+            RenderError::Submit(format!(
+                "{} times the {}x{} window is too big",
+                scale, self.dimensions[0], self.dimensions[1]
+            ))
+        };
+        let width = self.dimensions[0].checked_mul(scale).ok_or_else(too_big)?;
+        let height = self.dimensions[1].checked_mul(scale).ok_or_else(too_big)?;
+        Ok([width, height])
+    }
+
+    /// The largest screenshot scale the GPU can draw at the current window size
+    pub fn max_screenshot_scale(&self) -> u32 {
+        let max = self.renderer.max_offscreen_size();
+        (0..2)
+            .map(|axis| max[axis] / self.dimensions[axis].max(1))
+            .min()
+            .unwrap_or(1)
+    }
+}
+
+/// Creates a new `fractol_YYYY-MM-DD_HH-MM-SS.png` (UTC) in the current directory, with a
+/// number appended if taken. Never overwrites a file, even one that another screenshot
+/// finishing at the same time just created.
+fn create_screenshot_file() -> io::Result<(File, PathBuf)> {
+    let stem = screenshot_stem();
+    for n in 1.. {
+        let path = match n {
+            1 => PathBuf::from(format!("{}.png", stem)),
+            _ => PathBuf::from(format!("{}_{}.png", stem, n)),
+        };
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+/// `fractol_YYYY-MM-DD_HH-MM-SS` of now, in UTC
+fn screenshot_stem() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    let [year, month, day] = civil_from_days(days as i64);
+    format!(
+        "fractol_{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// Gregorian year, month and day of a number of days since 1970-01-01,
+/// from http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> [i64; 3] {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097); // [0, 146096]
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365; // [0, 399]
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); // [0, 365]
+    let mp = (5 * doy + 2) / 153; // [0, 11]
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    [year, month, day]
+}
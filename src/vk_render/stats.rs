@@ -0,0 +1,202 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Frames the rolling statistics are computed over
+const WINDOW: usize = 300;
+/// Frames a GPU time can arrive after its frame. Past that it is counted as missing
+const GPU_LATENCY: usize = 16;
+/// How often the statistics are printed, when asked for
+const REPORT_PERIOD: Duration = Duration::from_secs(5);
+
+/// What the render loop measured of a frame, in seconds
+#[derive(Clone, Copy)]
+pub struct FrameTimes {
+    /// from the start of the frame to the start of the next one
+    pub frame: f64,
+    /// waiting for a swapchain image
+    pub acquire: f64,
+    /// submitting the commands and presenting the image
+    pub present: f64,
+    /// running the commands on the GPU, None if it couldn't be measured
+    pub gpu: Option<f64>,
+}
+
+/// Rolling statistics of the last `WINDOW` frames, and optionally the times of every frame
+/// in a CSV file
+pub struct FrameStats {
+    /// waiting for their GPU time, with their frame number
+    pending: VecDeque<(u64, FrameTimes)>,
+    window: VecDeque<FrameTimes>,
+    csv: Option<BufWriter<File>>,
+    /// when the statistics were last printed, None if they are not
+    report: Option<Instant>,
+}
+
+impl FrameStats {
+    pub fn new() -> FrameStats {
+        FrameStats {
+            pending: VecDeque::new(),
+            window: VecDeque::with_capacity(WINDOW),
+            csv: None,
+            report: None,
+        }
+    }
+
+    /// Prints the statistics every `REPORT_PERIOD`
+    pub fn report(&mut self) {
+        self.report = Some(Instant::now());
+    }
+
+    /// Writes the times of each frame to `path`, in milliseconds
+    pub fn write_csv(&mut self, path: &Path) -> Result<(), String> {
+        let error = |e: std::io::Error| format!("{}: {}", path.display(), e);
+        let mut csv = BufWriter::new(File::create(path).map_err(error)?);
+        writeln!(csv, "frame,frame_ms,acquire_ms,present_ms,gpu_ms").map_err(error)?;
+        self.csv = Some(csv);
+        Ok(())
+    }
+
+    /// Records frame number `frame`, whose GPU time comes later through `gpu_time` if at all
+    pub fn record(&mut self, frame: u64, times: FrameTimes) {
+        self.pending.push_back((frame, times));
+        let late = self.pending.len().saturating_sub(GPU_LATENCY);
+        let late: Vec<_> = self.pending.drain(..late).collect();
+        for (frame, times) in late {
+            self.settle(frame, times);
+        }
+        if self
+            .report
+            .is_some_and(|last| last.elapsed() >= REPORT_PERIOD)
+        {
+            self.report = Some(Instant::now());
+            self.print_summary();
+        }
+    }
+
+    /// The GPU time of frame number `frame`, in seconds
+    pub fn gpu_time(&mut self, frame: u64, seconds: f64) {
+        // already settled, without it
+        if self.pending.front().is_none_or(|&(first, _)| frame < first) {
+            return;
+        }
+        // the frames before it are done too, if they have no time by now they won't have any
+        while let Some((pending, mut times)) = self.pending.pop_front() {
+            if pending == frame {
+                times.gpu = Some(seconds);
+            }
+            self.settle(pending, times);
+            if pending >= frame {
+                break;
+            }
+        }
+    }
+
+    /// Average GPU time of the last frames, in seconds
+    pub fn mean_gpu(&self) -> Option<f64> {
+        let times: Vec<f64> = self.window.iter().filter_map(|t| t.gpu).collect();
+        if times.is_empty() {
+            None
+        } else {
+            Some(times.iter().sum::<f64>() / times.len() as f64)
+        }
+    }
+
+    /// Writes what is left of the CSV, the frames still waiting for their GPU time without it
+    pub fn finish(&mut self) {
+        while let Some((frame, times)) = self.pending.pop_front() {
+            self.settle(frame, times);
+        }
+        if let Some(csv) = &mut self.csv {
+            if let Err(e) = csv.flush() {
+                eprintln!("Could not write the frame times: {}", e);
+            }
+        }
+    }
+
+    fn settle(&mut self, frame: u64, times: FrameTimes) {
+        if self.window.len() == WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(times);
+        if let Some(csv) = &mut self.csv {
+            let gpu = times
+                .gpu
+                .map_or(String::new(), |t| format!("{:.3}", t * 1e3));
+            let written = writeln!(
+                csv,
+                "{},{:.3},{:.3},{:.3},{}",
+                frame,
+                times.frame * 1e3,
+                times.acquire * 1e3,
+                times.present * 1e3,
+                gpu
+            );
+            if let Err(e) = written {
+                eprintln!("Could not write the frame times: {}", e);
+                self.csv = None;
+            }
+        }
+    }
+
+    fn print_summary(&self) {
+        let title = format!("Last {} frames (ms)", self.window.len());
+        println!("{:<20}  mean     p50     p95     p99", title);
+        let column = |value: fn(&FrameTimes) -> Option<f64>| -> Vec<f64> {
+            self.window.iter().filter_map(value).collect()
+        };
+        let rows = [
+            ("frame", column(|t| Some(t.frame))),
+            ("gpu", column(|t| t.gpu)),
+            ("acquire", column(|t| Some(t.acquire))),
+            ("present", column(|t| Some(t.present))),
+        ];
+        for (name, mut times) in rows {
+            if times.is_empty() {
+                continue;
+            }
+            times.sort_by(f64::total_cmp);
+            let mean = times.iter().sum::<f64>() / times.len() as f64;
+            let percentile = |p: f64| times[((times.len() - 1) as f64 * p).round() as usize];
+            println!(
+                "  {:<18}{:>6.2}  {:>6.2}  {:>6.2}  {:>6.2}",
+                name,
+                mean * 1e3,
+                percentile(0.5) * 1e3,
+                percentile(0.95) * 1e3,
+                percentile(0.99) * 1e3
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times() -> FrameTimes {
+        FrameTimes {
+            frame: 0.016,
+            acquire: 0.001,
+            present: 0.002,
+            gpu: None,
+        }
+    }
+
+    #[test]
+    fn late_gpu_time_leaves_the_pending_frames() {
+        let mut stats = FrameStats::new();
+        for frame in 0..4 {
+            stats.record(frame, times());
+        }
+        stats.gpu_time(1, 0.010);
+        // frame 0 was settled without a time by the result of frame 1
+        stats.gpu_time(0, 0.011);
+        stats.gpu_time(2, 0.012);
+        let gpu: Vec<Option<f64>> = stats.window.iter().map(|t| t.gpu).collect();
+        assert_eq!(gpu, [None, Some(0.010), Some(0.012)]);
+        assert_eq!(stats.pending.len(), 1);
+    }
+}